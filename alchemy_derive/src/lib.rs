@@ -0,0 +1,314 @@
+//!Derives for `alchemy_framework`, `Vertex` builds a vertex buffer layout from a struct and `Uniform`
+//!builds the bind group layout entry for a uniform buffer, checking std140 layout while compiling.
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Lit, Meta, NestedMeta, Type};
+
+///The field types a vertex or uniform can be made of.
+#[derive(Clone, Copy, PartialEq)]
+enum Scalar {
+    Float,
+    Uint,
+    Int,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Shape {
+    ///One to four components, `f32` or `[f32; N]`.
+    Vector(Scalar, usize),
+    ///`[[f32; 4]; 4]`
+    Matrix4,
+}
+
+impl Shape {
+    fn size(self) -> usize {
+        match self {
+            Shape::Vector(_, n) => 4 * n,
+            Shape::Matrix4 => 64,
+        }
+    }
+
+    ///Base alignment under std140.
+    fn std140_align(self) -> usize {
+        match self {
+            Shape::Vector(_, 1) => 4,
+            Shape::Vector(_, 2) => 8,
+            Shape::Vector(_, _) => 16,
+            Shape::Matrix4 => 16,
+        }
+    }
+}
+
+fn scalar(ty: &Type) -> Option<Scalar> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            let ident = path.path.get_ident()?;
+            if ident == "f32" {
+                Some(Scalar::Float)
+            } else if ident == "u32" {
+                Some(Scalar::Uint)
+            } else if ident == "i32" {
+                Some(Scalar::Int)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn array_len(ty: &syn::TypeArray) -> Option<usize> {
+    match &ty.len {
+        syn::Expr::Lit(syn::ExprLit { lit: Lit::Int(n), .. }) => n.base10_parse().ok(),
+        _ => None,
+    }
+}
+
+fn shape(ty: &Type) -> Option<Shape> {
+    if let Some(s) = scalar(ty) {
+        return Some(Shape::Vector(s, 1));
+    }
+    match ty {
+        Type::Array(array) => {
+            let len = array_len(array)?;
+            if let Some(s) = scalar(&array.elem) {
+                if (2..=4).contains(&len) {
+                    return Some(Shape::Vector(s, len));
+                }
+                return None;
+            }
+            match shape(&array.elem)? {
+                Shape::Vector(Scalar::Float, 4) if len == 4 => Some(Shape::Matrix4),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+const SUPPORTED: &str = "supported field types are f32, u32, i32, [T; 2..=4] of those, and [[f32; 4]; 4]";
+
+fn error(span: Span, message: &str) -> TokenStream {
+    syn::Error::new(span, message).to_compile_error().into()
+}
+
+fn is_repr_c(input: &DeriveInput) -> bool {
+    input.attrs.iter().filter(|attr| attr.path.is_ident("repr")).any(|attr| match attr.parse_meta() {
+        Ok(Meta::List(list)) => list.nested.iter().any(|nested| match nested {
+            NestedMeta::Meta(Meta::Path(path)) => path.is_ident("C"),
+            _ => false,
+        }),
+        _ => false,
+    })
+}
+
+///Alignments are all powers of two.
+fn is_aligned(offset: usize, align: usize) -> bool {
+    offset & (align - 1) == 0
+}
+
+///Reads the named fields and their shapes, or returns the error to emit.
+fn fields(input: &DeriveInput, derive: &str) -> Result<Vec<(syn::Field, Shape)>, TokenStream> {
+    if !is_repr_c(input) {
+        return Err(error(input.ident.span(), &format!("#[derive({})] requires #[repr(C)]", derive)));
+    }
+    let named = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => &named.named,
+            _ => return Err(error(input.ident.span(), &format!("#[derive({})] needs a struct with named fields", derive))),
+        },
+        _ => return Err(error(input.ident.span(), &format!("#[derive({})] only works on structs", derive))),
+    };
+
+    let mut out = Vec::new();
+    for field in named {
+        match shape(&field.ty) {
+            Some(shape) => out.push((field.clone(), shape)),
+            None => {
+                let span = field.ident.as_ref().map(|i| i.span()).unwrap_or_else(Span::call_site);
+                return Err(error(span, SUPPORTED));
+            }
+        }
+    }
+    Ok(out)
+}
+
+///Collects the `name` and `name = value` items of `#[attr(...)]` on the struct.
+fn options(input: &DeriveInput, attr: &str) -> Result<Vec<NestedMeta>, TokenStream> {
+    let mut out = Vec::new();
+    for a in input.attrs.iter().filter(|a| a.path.is_ident(attr)) {
+        match a.parse_meta() {
+            Ok(Meta::List(list)) => out.extend(list.nested),
+            Ok(_) => return Err(error(a.path.get_ident().unwrap().span(), &format!("expected #[{}(...)]", attr))),
+            Err(e) => return Err(e.to_compile_error().into()),
+        }
+    }
+    Ok(out)
+}
+
+///Derives `alchemy_framework::mesh::Vertex`.
+///
+///Fields become attributes at consecutive shader locations, a `[[f32; 4]; 4]` takes four.
+///`#[vertex(instance)]` steps the buffer per instance and `#[vertex(location = N)]` starts at location N.
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let fields = match fields(&input, "Vertex") {
+        Ok(fields) => fields,
+        Err(e) => return e,
+    };
+
+    let mut instance = false;
+    let mut location = 0u32;
+    let opts = match options(&input, "vertex") {
+        Ok(opts) => opts,
+        Err(e) => return e,
+    };
+    for opt in opts {
+        match opt {
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("instance") => instance = true,
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("location") => match &nv.lit {
+                Lit::Int(n) => match n.base10_parse() {
+                    Ok(n) => location = n,
+                    Err(e) => return e.to_compile_error().into(),
+                },
+                other => return error(other.span(), "location must be an integer"),
+            },
+            other => return error(syn::spanned::Spanned::span(&other), "expected `instance` or `location = N`"),
+        }
+    }
+
+    let mut attributes = Vec::new();
+    let mut offset = 0usize;
+    for (_, shape) in &fields {
+        let (format, count) = match shape {
+            Shape::Vector(s, n) => {
+                let name = match (s, n) {
+                    (Scalar::Float, 1) => "Float",
+                    (Scalar::Float, 2) => "Float2",
+                    (Scalar::Float, 3) => "Float3",
+                    (Scalar::Float, _) => "Float4",
+                    (Scalar::Uint, 1) => "Uint",
+                    (Scalar::Uint, 2) => "Uint2",
+                    (Scalar::Uint, 3) => "Uint3",
+                    (Scalar::Uint, _) => "Uint4",
+                    (Scalar::Int, 1) => "Int",
+                    (Scalar::Int, 2) => "Int2",
+                    (Scalar::Int, 3) => "Int3",
+                    (Scalar::Int, _) => "Int4",
+                };
+                (name, 1)
+            }
+            Shape::Matrix4 => ("Float4", 4),
+        };
+        let format = syn::Ident::new(format, Span::call_site());
+        for column in 0..count {
+            let column_offset = (offset + column * 16) as u64;
+            attributes.push(quote! {
+                ::alchemy_framework::wgpu::VertexAttribute {
+                    offset: #column_offset,
+                    shader_location: #location,
+                    format: ::alchemy_framework::wgpu::VertexFormat::#format,
+                }
+            });
+            location += 1;
+        }
+        offset += shape.size();
+    }
+
+    let name = &input.ident;
+    let count = attributes.len();
+    let stride = offset;
+    let step_mode = if instance {
+        quote! { ::alchemy_framework::wgpu::InputStepMode::Instance }
+    } else {
+        quote! { ::alchemy_framework::wgpu::InputStepMode::Vertex }
+    };
+
+    let expanded = quote! {
+        //Every supported field is 4 byte aligned so #[repr(C)] adds no padding, this catches anything that would.
+        const _: [(); #stride] = [(); ::std::mem::size_of::<#name>()];
+
+        impl ::alchemy_framework::mesh::Vertex for #name {
+            fn desc() -> ::alchemy_framework::wgpu::VertexBufferLayout<'static> {
+                const ATTRIBUTES: [::alchemy_framework::wgpu::VertexAttribute; #count] = [#(#attributes),*];
+                ::alchemy_framework::wgpu::VertexBufferLayout {
+                    array_stride: #stride as ::alchemy_framework::wgpu::BufferAddress,
+                    step_mode: #step_mode,
+                    attributes: &ATTRIBUTES,
+                }
+            }
+        }
+    };
+    expanded.into()
+}
+
+///Derives `alchemy_framework::camera::Uniform`.
+///
+///Fails to compile when a field's offset breaks std140 alignment or the struct size isn't a multiple of 16,
+///pad with explicit fields to fix it. `#[uniform(vertex)]`, `#[uniform(fragment)]` and `#[uniform(compute)]`
+///pick the visible stages, vertex and fragment when none are given.
+#[proc_macro_derive(Uniform, attributes(uniform))]
+pub fn derive_uniform(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let fields = match fields(&input, "Uniform") {
+        Ok(fields) => fields,
+        Err(e) => return e,
+    };
+
+    let mut stages = Vec::new();
+    let opts = match options(&input, "uniform") {
+        Ok(opts) => opts,
+        Err(e) => return e,
+    };
+    for opt in opts {
+        match opt {
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("vertex") => stages.push(quote! { VERTEX }),
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("fragment") => stages.push(quote! { FRAGMENT }),
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("compute") => stages.push(quote! { COMPUTE }),
+            other => return error(syn::spanned::Spanned::span(&other), "expected `vertex`, `fragment` or `compute`"),
+        }
+    }
+    if stages.is_empty() {
+        stages.push(quote! { VERTEX });
+        stages.push(quote! { FRAGMENT });
+    }
+
+    let mut offset = 0usize;
+    for (field, shape) in &fields {
+        let align = shape.std140_align();
+        if !is_aligned(offset, align) {
+            let span = field.ident.as_ref().map(|i| i.span()).unwrap_or_else(Span::call_site);
+            return error(span, &format!("field at offset {} breaks std140, it needs {} byte alignment", offset, align));
+        }
+        offset += shape.size();
+    }
+    if !is_aligned(offset, 16) {
+        return error(input.ident.span(), &format!("uniform is {} bytes, std140 needs a multiple of 16", offset));
+    }
+
+    let name = &input.ident;
+    let size = offset;
+    let expanded = quote! {
+        const _: [(); #size] = [(); ::std::mem::size_of::<#name>()];
+
+        impl ::alchemy_framework::camera::Uniform for #name {
+            fn layout_entry(binding: u32) -> ::alchemy_framework::wgpu::BindGroupLayoutEntry {
+                ::alchemy_framework::wgpu::BindGroupLayoutEntry {
+                    binding,
+                    visibility: #(::alchemy_framework::wgpu::ShaderStage::#stages)|*,
+                    ty: ::alchemy_framework::wgpu::BindingType::Buffer {
+                        ty: ::alchemy_framework::wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: ::std::num::NonZeroU64::new(#size as u64),
+                    },
+                    count: None,
+                }
+            }
+        }
+    };
+    expanded.into()
+}
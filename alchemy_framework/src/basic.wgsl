@@ -0,0 +1,26 @@
+[[block]]
+struct Uniforms {
+    view_position: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> u_camera: Uniforms;
+
+[[builtin(vertex_index)]] var<in> in_vertex_index: u32;
+[[builtin(position)]] var<out> out_position: vec4<f32>;
+
+[[stage(vertex)]]
+fn vs_main() {
+    //The built in triangle, (0, 0.5), (-0.5, -0.5) and (0.5, -0.5).
+    const i: i32 = i32(in_vertex_index);
+    const x: f32 = f32((i & 2) / 2 - (i & 1)) * 0.5;
+    const y: f32 = f32(1 - 2 * ((i | (i >> 1)) & 1)) * 0.5;
+    out_position = u_camera.view_proj * vec4<f32>(x, y, 0.0, 1.0);
+}
+
+[[location(0)]] var<out> out_color_fs: vec4<f32>;
+
+[[stage(fragment)]]
+fn fs_main() {
+    out_color_fs = vec4<f32>(0.3, 0.2, 0.1, 1.0);
+}
@@ -3,9 +3,27 @@ use std::f32::consts::FRAC_PI_2;
 use std::time::Duration;
 use winit::dpi::PhysicalPosition;
 use winit::event::*;
+use crate::input::{Action, Binding, Bindings};
+
+///Just short of straight up or down, where the view matrix loses its up vector.
+const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
+
+pub use alchemy_derive::Uniform;
+
+///A `#[repr(C)]` uniform block, usually derived with `#[derive(Uniform)]`.
+pub trait Uniform: bytemuck::Pod {
+    fn layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry;
+
+    fn create_layout(device: &wgpu::Device, binding: u32, label: &str) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[Self::layout_entry(binding)],
+            label: Some(label),
+        })
+    }
+}
 
 #[repr(C)]
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Debug)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Debug, Uniform)]
 pub struct Uniforms {
     view_position: [f32; 4],
     view_proj: [[f32; 4]; 4],
@@ -20,7 +38,7 @@ impl Uniforms {
     }
 
     // UPDATED!
-    pub fn update_view_proj(&mut self, camera: &Camera, projection: &Projection) {
+    pub fn update_view_proj<P: CameraProjection + ?Sized>(&mut self, camera: &Camera, projection: &P) {
         self.view_position = camera.position.to_homogeneous().into();
         self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into()
     }
@@ -34,18 +52,36 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.5, 1.0,
 );
 
-pub struct CameraObject{
+///A camera with its projection, the controller that moves it and the uniforms it's uploaded with.
+///Defaults to the free-fly `CameraController` and a perspective `Projection`,
+///`with_controller` and `with_projection` take any others.
+pub struct CameraObject<C = CameraController, P = Projection>{
     pub camera: Camera,
-    pub projection: Projection,
-    pub controller: CameraController,
+    pub projection: P,
+    pub controller: C,
     pub uniforms: Uniforms,
 }
 
 impl CameraObject{
     pub fn new(sc_desc: &wgpu::SwapChainDescriptor) -> Self{
-        let projection = crate::camera::Projection::new(sc_desc.width, sc_desc.height, cgmath::Deg(45.0), 0.001, 100.0);
         let controller = crate::camera::CameraController::new(4.0, 0.4);
-        let camera = crate::camera::Camera::new((0.0, 5.0, 10.0), cgmath::Deg(-90.0), cgmath::Deg(-20.0));      
+        Self::with_controller(sc_desc, controller)
+    }
+}
+
+impl<C: CameraControl> CameraObject<C>{
+    pub fn with_controller(sc_desc: &wgpu::SwapChainDescriptor, controller: C) -> Self{
+        let projection = crate::camera::Projection::new(sc_desc.width, sc_desc.height, cgmath::Deg(45.0), 0.001, 100.0);
+        Self::with_projection(controller, projection)
+    }
+}
+
+impl<C: CameraControl, P: CameraProjection> CameraObject<C, P>{
+    ///The controller is applied once straight away, so controllers that own the camera's placement
+    ///like `OrbitController` start out where they say.
+    pub fn with_projection(mut controller: C, projection: P) -> Self{
+        let mut camera = crate::camera::Camera::new((0.0, 5.0, 10.0), cgmath::Deg(-90.0), cgmath::Deg(-20.0));
+        controller.update_camera(&mut camera, Duration::from_secs(0));
         Self {
             camera,
             projection,
@@ -109,6 +145,14 @@ impl<T: bytemuck::Pod> GPUObject<T> {
     }
 }
 
+impl<T: Uniform> GPUObject<T> {
+    ///Same as `new` but builds the layout from the uniform type.
+    pub fn from_uniform(device: &wgpu::Device, uniforms: T, binding: u32, name: &str) -> Self{
+        let layout = T::create_layout(device, binding, format!("BindGroupLayout-{}", name).as_str());
+        Self::new(device, layout, uniforms, binding, name)
+    }
+}
+
 #[derive(Debug)]
 pub struct Camera {
     pub position: Point3<f32>,
@@ -129,15 +173,39 @@ impl Camera {
         }
     }
 
+    ///Unit vector the camera looks along. Pitch only tilts the horizontal direction rather than rotating it,
+    ///so the view rises a bit less than `pitch` itself, the way the camera has always looked.
+    pub fn forward(&self) -> Vector3<f32> {
+        Vector3::new(self.yaw.0.cos(), self.pitch.0.sin(), self.yaw.0.sin()).normalize()
+    }
+
     pub fn calc_matrix(&self) -> Matrix4<f32> {
         Matrix4::look_to_rh(
             self.position,
-            Vector3::new(self.yaw.0.cos(), self.pitch.0.sin(), self.yaw.0.sin()).normalize(),
+            self.forward(),
             Vector3::unit_y(),
         )
     }
 }
 
+///Builds the projection half of the view projection matrix, kept up to date with the window size.
+pub trait CameraProjection {
+    ///Called with the new window size, a minimized window reports zero and should be ignored.
+    fn resize(&mut self, width: u32, height: u32);
+    fn calc_matrix(&self) -> Matrix4<f32>;
+}
+
+impl<P: CameraProjection + ?Sized> CameraProjection for Box<P> {
+    fn resize(&mut self, width: u32, height: u32) {
+        (**self).resize(width, height);
+    }
+
+    fn calc_matrix(&self) -> Matrix4<f32> {
+        (**self).calc_matrix()
+    }
+}
+
+///Perspective projection.
 #[derive(Debug)]
 pub struct Projection {
     aspect: f32,
@@ -148,31 +216,163 @@ pub struct Projection {
 
 impl Projection {
     pub fn new<F: Into<Rad<f32>>>(width: u32, height: u32, fovy: F, znear: f32, zfar: f32) -> Self {
-        Self {
-            aspect: width as f32 / height as f32,
+        let mut projection = Self {
+            aspect: 1.0,
             fovy: fovy.into(),
             znear,
             zfar,
-        }
+        };
+        projection.resize(width, height);
+        projection
     }
+}
 
-    pub fn resize(&mut self, width: u32, height: u32) {
+impl CameraProjection for Projection {
+    fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
         self.aspect = width as f32 / height as f32;
     }
 
-    pub fn calc_matrix(&self) -> Matrix4<f32> {
+    fn calc_matrix(&self) -> Matrix4<f32> {
         OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.znear, self.zfar)
     }
 }
 
+///Where the origin of a pixel space orthographic projection sits on screen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Origin {
+    ///Y grows downwards, like window coordinates.
+    TopLeft,
+    BottomLeft,
+    Center,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrthographicMode {
+    ///Always shows this many world units from bottom to top, the width follows the aspect ratio.
+    FixedHeight(f32),
+    ///One world unit per pixel.
+    Pixel(Origin),
+}
+
+///Orthographic projection, for 2D or for editor views without perspective.
+#[derive(Debug)]
+pub struct OrthographicProjection {
+    pub mode: OrthographicMode,
+    width: f32,
+    height: f32,
+    znear: f32,
+    zfar: f32,
+}
+
+impl OrthographicProjection {
+    pub fn new(width: u32, height: u32, mode: OrthographicMode, znear: f32, zfar: f32) -> Self {
+        let mut projection = Self {
+            mode,
+            width: 1.0,
+            height: 1.0,
+            znear,
+            zfar,
+        };
+        projection.resize(width, height);
+        projection
+    }
+}
+
+impl CameraProjection for OrthographicProjection {
+    fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.width = width as f32;
+        self.height = height as f32;
+    }
+
+    fn calc_matrix(&self) -> Matrix4<f32> {
+        let (left, right, bottom, top) = match self.mode {
+            OrthographicMode::FixedHeight(units) => {
+                let half_height = units / 2.0;
+                let half_width = half_height * self.width / self.height;
+                (-half_width, half_width, -half_height, half_height)
+            }
+            OrthographicMode::Pixel(Origin::TopLeft) => (0.0, self.width, self.height, 0.0),
+            OrthographicMode::Pixel(Origin::BottomLeft) => (0.0, self.width, 0.0, self.height),
+            OrthographicMode::Pixel(Origin::Center) => {
+                //Rounded so pixels stay on whole coordinates when a side is odd.
+                let left = (self.width / 2.0).floor();
+                let bottom = (self.height / 2.0).floor();
+                (-left, self.width - left, -bottom, self.height - bottom)
+            }
+        };
+        OPENGL_TO_WGPU_MATRIX * ortho(left, right, bottom, top, self.znear, self.zfar)
+    }
+}
+
+///Turns input into camera movement, `CameraObject` drives whichever one it holds.
+pub trait CameraControl {
+    ///Returns true when the key was used.
+    fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool;
+    ///Takes the raw button id from `DeviceEvent::Button`, returns true when the button was used.
+    fn process_button(&mut self, _button: u32, _state: ElementState) -> bool {
+        false
+    }
+    fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64);
+    fn process_scroll(&mut self, delta: &MouseScrollDelta);
+    fn update_camera(&mut self, camera: &mut Camera, dt: Duration);
+    ///Forgets everything held, for when focus is lost and the releases will never arrive.
+    fn reset(&mut self) {}
+}
+
+fn scroll_amount(delta: &MouseScrollDelta) -> f32 {
+    match delta {
+        // I'm assuming a line is about 100 pixels
+        MouseScrollDelta::LineDelta(_, scroll) => -scroll * 0.5,
+        MouseScrollDelta::PixelDelta(PhysicalPosition { y: scroll, .. }) => -*scroll as f32,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CameraAction {
+    Forward,
+    Backward,
+    Left,
+    Right,
+    Up,
+    Down,
+    ///The mouse only turns the camera while this is held, or always when it has no bindings.
+    Look,
+}
+
+impl Action for CameraAction {
+    const ALL: &'static [Self] = &[
+        CameraAction::Forward,
+        CameraAction::Backward,
+        CameraAction::Left,
+        CameraAction::Right,
+        CameraAction::Up,
+        CameraAction::Down,
+        CameraAction::Look,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            CameraAction::Forward => "forward",
+            CameraAction::Backward => "backward",
+            CameraAction::Left => "left",
+            CameraAction::Right => "right",
+            CameraAction::Up => "up",
+            CameraAction::Down => "down",
+            CameraAction::Look => "look",
+        }
+    }
+}
+
+///Free-fly movement with mouse look, WASD and the arrows by default.
 #[derive(Debug)]
 pub struct CameraController {
-    amount_left: f32,
-    amount_right: f32,
-    amount_forward: f32,
-    amount_backward: f32,
-    amount_up: f32,
-    amount_down: f32,
+    pub bindings: Bindings<CameraAction>,
     rotate_horizontal: f32,
     rotate_vertical: f32,
     scroll: f32,
@@ -183,12 +383,7 @@ pub struct CameraController {
 impl CameraController {
     pub fn new(speed: f32, sensitivity: f32) -> Self {
         Self {
-            amount_left: 0.0,
-            amount_right: 0.0,
-            amount_forward: 0.0,
-            amount_backward: 0.0,
-            amount_up: 0.0,
-            amount_down: 0.0,
+            bindings: Self::default_bindings(),
             rotate_horizontal: 0.0,
             rotate_vertical: 0.0,
             scroll: 0.0,
@@ -197,63 +392,67 @@ impl CameraController {
         }
     }
 
-    pub fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
-        let amount = if state == ElementState::Pressed {
+    pub fn default_bindings() -> Bindings<CameraAction> {
+        use VirtualKeyCode as Key;
+        Bindings::from_pairs(&[
+            (CameraAction::Forward, &[Binding::Key(Key::W), Binding::Key(Key::Up)]),
+            (CameraAction::Backward, &[Binding::Key(Key::S), Binding::Key(Key::Down)]),
+            (CameraAction::Left, &[Binding::Key(Key::A), Binding::Key(Key::Left)]),
+            (CameraAction::Right, &[Binding::Key(Key::D), Binding::Key(Key::Right)]),
+            (CameraAction::Up, &[Binding::Key(Key::Space)]),
+            (CameraAction::Down, &[Binding::Key(Key::LShift)]),
+            (CameraAction::Look, &[Binding::Button(1)]),
+        ])
+    }
+
+    fn amount(&self, action: CameraAction) -> f32 {
+        if self.bindings.is_held(action) {
             1.0
         } else {
             0.0
-        };
-        match key {
-            VirtualKeyCode::W | VirtualKeyCode::Up => {
-                self.amount_forward = amount;
-                true
-            }
-            VirtualKeyCode::S | VirtualKeyCode::Down => {
-                self.amount_backward = amount;
-                true
-            }
-            VirtualKeyCode::A | VirtualKeyCode::Left => {
-                self.amount_left = amount;
-                true
-            }
-            VirtualKeyCode::D | VirtualKeyCode::Right => {
-                self.amount_right = amount;
-                true
-            }
-            VirtualKeyCode::Space => {
-                self.amount_up = amount;
-                true
-            }
-            VirtualKeyCode::LShift => {
-                self.amount_down = amount;
-                true
-            }
-            _ => false,
         }
     }
+}
+
+impl CameraControl for CameraController {
+    fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        self.bindings.handle(Binding::Key(key), state)
+    }
+
+    fn process_button(&mut self, button: u32, state: ElementState) -> bool {
+        self.bindings.handle(Binding::Button(button), state)
+    }
 
-    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+    fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        if !self.bindings.get(CameraAction::Look).is_empty() && !self.bindings.is_held(CameraAction::Look) {
+            return;
+        }
         self.rotate_horizontal = mouse_dx as f32;
         self.rotate_vertical = mouse_dy as f32;
     }
 
-    pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
-        self.scroll = match delta {
-            // I'm assuming a line is about 100 pixels
-            MouseScrollDelta::LineDelta(_, scroll) => -scroll * 0.5,
-            MouseScrollDelta::PixelDelta(PhysicalPosition { y: scroll, .. }) => -*scroll as f32,
-        };
+    fn process_scroll(&mut self, delta: &MouseScrollDelta) {
+        self.scroll = scroll_amount(delta);
+    }
+
+    fn reset(&mut self) {
+        self.bindings.release_all();
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+        self.scroll = 0.0;
     }
 
-    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+    fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
         let dt = dt.as_secs_f32();
 
         // Move forward/backward and left/right
         let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
         let forward = Vector3::new(yaw_cos, 0.0, yaw_sin).normalize();
         let right = Vector3::new(-yaw_sin, 0.0, yaw_cos).normalize();
-        camera.position += forward * (self.amount_forward - self.amount_backward) * self.speed * dt;
-        camera.position += right * (self.amount_right - self.amount_left) * self.speed * dt;
+        let forward_amount = self.amount(CameraAction::Forward) - self.amount(CameraAction::Backward);
+        let right_amount = self.amount(CameraAction::Right) - self.amount(CameraAction::Left);
+        camera.position += forward * forward_amount * self.speed * dt;
+        camera.position += right * right_amount * self.speed * dt;
 
         // Move in/out (aka. "zoom")
         // Note: this isn't an actual zoom. The camera's position
@@ -267,7 +466,7 @@ impl CameraController {
 
         // Move up/down. Since we don't use roll, we can just
         // modify the y coordinate directly.
-        camera.position.y += (self.amount_up - self.amount_down) * self.speed * dt;
+        camera.position.y += (self.amount(CameraAction::Up) - self.amount(CameraAction::Down)) * self.speed * dt;
 
         // Rotate
         camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
@@ -280,10 +479,200 @@ impl CameraController {
         self.rotate_vertical = 0.0;
 
         // Keep the camera's angle from going too high/low.
-        if camera.pitch < -Rad(FRAC_PI_2) {
-            camera.pitch = -Rad(FRAC_PI_2);
-        } else if camera.pitch > Rad(FRAC_PI_2) {
-            camera.pitch = Rad(FRAC_PI_2);
+        if camera.pitch < -Rad(SAFE_FRAC_PI_2) {
+            camera.pitch = -Rad(SAFE_FRAC_PI_2);
+        } else if camera.pitch > Rad(SAFE_FRAC_PI_2) {
+            camera.pitch = Rad(SAFE_FRAC_PI_2);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrbitAction {
+    Rotate,
+    ///Takes priority over `Rotate` when both are held.
+    Pan,
+}
+
+impl Action for OrbitAction {
+    const ALL: &'static [Self] = &[OrbitAction::Rotate, OrbitAction::Pan];
+
+    fn name(self) -> &'static str {
+        match self {
+            OrbitAction::Rotate => "rotate",
+            OrbitAction::Pan => "pan",
         }
     }
-}
\ No newline at end of file
+}
+
+///Orbits around `target`, dragging with the rotate binding held turns around it, with the pan binding held
+///it moves the target in the view plane, and scrolling dollies in and out between the distance limits.
+#[derive(Debug)]
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub min_pitch: Rad<f32>,
+    pub max_pitch: Rad<f32>,
+    pub bindings: Bindings<OrbitAction>,
+    yaw: Rad<f32>,
+    pitch: Rad<f32>,
+    mouse_dx: f32,
+    mouse_dy: f32,
+    scroll: f32,
+    sensitivity: f32,
+    ///Fraction of the distance moved per second of scrolling.
+    zoom_speed: f32,
+}
+
+impl OrbitController {
+    pub fn new<P: Into<Point3<f32>>>(target: P, distance: f32, sensitivity: f32) -> Self {
+        Self {
+            target: target.into(),
+            distance,
+            min_distance: 0.1,
+            max_distance: 1000.0,
+            min_pitch: Rad(-SAFE_FRAC_PI_2),
+            max_pitch: Rad(SAFE_FRAC_PI_2),
+            bindings: Self::default_bindings(),
+            yaw: Deg(-90.0).into(),
+            pitch: Deg(-20.0).into(),
+            mouse_dx: 0.0,
+            mouse_dy: 0.0,
+            scroll: 0.0,
+            sensitivity,
+            zoom_speed: 4.0,
+        }
+    }
+
+    ///Left button rotates and right button pans, as X11 and Windows number them.
+    pub fn default_bindings() -> Bindings<OrbitAction> {
+        Bindings::from_pairs(&[
+            (OrbitAction::Rotate, &[Binding::Button(1)]),
+            (OrbitAction::Pan, &[Binding::Button(3)]),
+        ])
+    }
+
+    pub fn with_distance_limits(mut self, min: f32, max: f32) -> Self {
+        self.min_distance = min;
+        self.max_distance = max;
+        self
+    }
+
+    pub fn with_pitch_limits<A: Into<Rad<f32>>>(mut self, min: A, max: A) -> Self {
+        self.min_pitch = min.into();
+        self.max_pitch = max.into();
+        self
+    }
+
+    pub fn with_angles<Y: Into<Rad<f32>>, P: Into<Rad<f32>>>(mut self, yaw: Y, pitch: P) -> Self {
+        self.yaw = yaw.into();
+        self.pitch = pitch.into();
+        self
+    }
+}
+
+impl CameraControl for OrbitController {
+    fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
+        self.bindings.handle(Binding::Key(key), state)
+    }
+
+    fn process_button(&mut self, button: u32, state: ElementState) -> bool {
+        self.bindings.handle(Binding::Button(button), state)
+    }
+
+    fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        self.mouse_dx = mouse_dx as f32;
+        self.mouse_dy = mouse_dy as f32;
+    }
+
+    fn process_scroll(&mut self, delta: &MouseScrollDelta) {
+        self.scroll = scroll_amount(delta);
+    }
+
+    fn reset(&mut self) {
+        self.bindings.release_all();
+        self.mouse_dx = 0.0;
+        self.mouse_dy = 0.0;
+        self.scroll = 0.0;
+    }
+
+    fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+        let dt = dt.as_secs_f32();
+
+        if self.bindings.is_held(OrbitAction::Pan) {
+            //Scaled by distance so the target keeps up with the cursor however far out the camera is.
+            let forward = camera.forward();
+            let right = forward.cross(Vector3::unit_y()).normalize();
+            let up = right.cross(forward);
+            let pan = (up * self.mouse_dy - right * self.mouse_dx) * self.sensitivity * self.distance * dt * 0.1;
+            self.target += pan;
+        } else if self.bindings.is_held(OrbitAction::Rotate) {
+            self.yaw += Rad(self.mouse_dx) * self.sensitivity * dt;
+            self.pitch += Rad(-self.mouse_dy) * self.sensitivity * dt;
+        }
+        self.mouse_dx = 0.0;
+        self.mouse_dy = 0.0;
+
+        self.distance += self.scroll * self.zoom_speed * self.distance * dt;
+        self.scroll = 0.0;
+
+        self.distance = self.distance.max(self.min_distance).min(self.max_distance);
+        if self.pitch < self.min_pitch {
+            self.pitch = self.min_pitch;
+        } else if self.pitch > self.max_pitch {
+            self.pitch = self.max_pitch;
+        }
+
+        camera.yaw = self.yaw;
+        camera.pitch = self.pitch;
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orbit_stays_on_target_within_limits() {
+        let mut controller = OrbitController::new((1.0, 2.0, 3.0), 5.0, 1.0)
+            .with_distance_limits(2.0, 10.0)
+            .with_pitch_limits(Deg(-45.0), Deg(45.0));
+        let mut camera = Camera::new((0.0, 0.0, 0.0), Deg(0.0), Deg(0.0));
+
+        controller.process_button(1, ElementState::Pressed);
+        controller.process_mouse(100.0, -1000.0);
+        controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, -100.0));
+        controller.update_camera(&mut camera, Duration::from_secs(1));
+
+        assert_eq!(controller.distance, 10.0);
+        assert!((Rad(camera.pitch.0) - Rad::from(Deg(45.0))).0.abs() < 1e-6);
+        let to_target = controller.target - camera.position;
+        assert!((to_target.magnitude() - 10.0).abs() < 1e-4);
+        assert!((to_target.normalize() - camera.forward()).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn camera_looks_where_it_always_did() {
+        let camera = Camera::new((0.0, 0.0, 0.0), Deg(90.0), Deg(30.0));
+        let expected = Vector3::new(0.0, 0.5, 1.0).normalize();
+        assert!((camera.forward() - expected).magnitude() < 1e-6);
+
+        let looked_at = camera.calc_matrix() * expected.extend(0.0);
+        assert!((looked_at.truncate() - Vector3::new(0.0, 0.0, -1.0)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn pixel_orthographic_maps_window_corners() {
+        let mut projection = OrthographicProjection::new(800, 600, OrthographicMode::Pixel(Origin::TopLeft), 0.0, 1.0);
+        projection.resize(0, 0);
+        let matrix = projection.calc_matrix();
+
+        let top_left = matrix * Vector4::new(0.0, 0.0, 0.0, 1.0);
+        let bottom_right = matrix * Vector4::new(800.0, 600.0, 0.0, 1.0);
+        assert_eq!((top_left.x, top_left.y), (-1.0, 1.0));
+        assert_eq!((bottom_right.x, bottom_right.y), (1.0, -1.0));
+    }
+}
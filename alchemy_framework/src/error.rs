@@ -0,0 +1,127 @@
+use std::fmt;
+
+///Everything that can go wrong inside the framework, so callers can report it instead of aborting.
+#[derive(Debug)]
+pub enum Error {
+    ///No adapter matched the requested backends and surface.
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    WindowBuild(winit::error::OsError),
+    ///Rendering was attempted before an effect was added to the state.
+    MissingEffect,
+    ///An image asset could not be loaded or saved.
+    Image(image::ImageError),
+    ///An OBJ model or its MTL library could not be parsed.
+    Obj(tobj::LoadError),
+    ///A glTF file, or a buffer or image it references, could not be read.
+    Gltf(gltf::Error),
+    SwapChain(wgpu::SwapChainError),
+    ///Reading a frame back from the gpu failed.
+    BufferMap(wgpu::BufferAsyncError),
+    Io(std::io::Error),
+    ///Frames in this format can't be read back, captures need 8 bit RGBA or BGRA.
+    UnsupportedFormat(wgpu::TextureFormat),
+    ///A line of an input bindings config could not be understood.
+    Bindings { line: usize, message: String },
+    ///A pass names a texture or pass the render graph doesn't have, or passes depend on each other in a cycle.
+    RenderGraph(String),
+    ///A shader failed to parse or validate, `line` is where when the parser knows.
+    Shader { name: String, line: Option<usize>, message: String },
+    ///Shader files couldn't be watched for hot reloading.
+    Watch(notify::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoAdapter => write!(f, "no suitable graphics adapter was found"),
+            Error::RequestDevice(e) => write!(f, "failed to request a graphics device: {}", e),
+            Error::WindowBuild(e) => write!(f, "failed to build the window: {}", e),
+            Error::MissingEffect => write!(f, "no effect was added before rendering"),
+            Error::Image(e) => write!(f, "failed to load or save an image: {}", e),
+            Error::Obj(e) => write!(f, "failed to load an OBJ model: {}", e),
+            Error::Gltf(e) => write!(f, "failed to load a glTF scene: {}", e),
+            Error::SwapChain(e) => write!(f, "swap chain error: {}", e),
+            Error::BufferMap(e) => write!(f, "{}", e),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::UnsupportedFormat(format) => write!(f, "can't capture frames in {:?}", format),
+            Error::Bindings { line, message } => write!(f, "invalid binding on line {}: {}", line, message),
+            Error::RenderGraph(message) => write!(f, "render graph error: {}", message),
+            Error::Shader { name, line: Some(line), message } => write!(f, "shader {} line {}: {}", name, line, message),
+            Error::Shader { name, line: None, message } => write!(f, "shader {}: {}", name, message),
+            Error::Watch(e) => write!(f, "failed to watch shader files: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::RequestDevice(e) => Some(e),
+            Error::WindowBuild(e) => Some(e),
+            Error::Image(e) => Some(e),
+            Error::Obj(e) => Some(e),
+            Error::Gltf(e) => Some(e),
+            Error::SwapChain(e) => Some(e),
+            Error::BufferMap(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Watch(e) => Some(e),
+            Error::NoAdapter | Error::MissingEffect | Error::UnsupportedFormat(_) | Error::Bindings { .. }
+                | Error::RenderGraph(_) | Error::Shader { .. } => None,
+        }
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for Error {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        Error::RequestDevice(e)
+    }
+}
+
+impl From<winit::error::OsError> for Error {
+    fn from(e: winit::error::OsError) -> Self {
+        Error::WindowBuild(e)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Image(e)
+    }
+}
+
+impl From<tobj::LoadError> for Error {
+    fn from(e: tobj::LoadError) -> Self {
+        Error::Obj(e)
+    }
+}
+
+impl From<gltf::Error> for Error {
+    fn from(e: gltf::Error) -> Self {
+        Error::Gltf(e)
+    }
+}
+
+impl From<wgpu::SwapChainError> for Error {
+    fn from(e: wgpu::SwapChainError) -> Self {
+        Error::SwapChain(e)
+    }
+}
+
+impl From<wgpu::BufferAsyncError> for Error {
+    fn from(e: wgpu::BufferAsyncError) -> Self {
+        Error::BufferMap(e)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<notify::Error> for Error {
+    fn from(e: notify::Error) -> Self {
+        Error::Watch(e)
+    }
+}
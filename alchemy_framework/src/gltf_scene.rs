@@ -0,0 +1,402 @@
+//!glTF 2.0 import, `.gltf` with external or embedded resources and binary `.glb`.
+//!`SceneData` is read entirely on the cpu, `GpuScene::upload` turns it into models ready to draw.
+use crate::camera;
+use crate::mesh::{Indices, Mesh};
+use crate::model::{self, Material, Model, ModelMesh, ModelVertex};
+use crate::texture;
+use crate::Error;
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector4};
+use gltf::json::validation::Error as ValidationError;
+use gltf::json::Path as JsonPath;
+use std::ops::Range;
+use std::path::Path;
+
+///Used for perspective cameras that leave `zfar` out, meaning an infinite projection.
+pub const DEFAULT_ZFAR: f32 = 1000.0;
+
+pub struct Node {
+    pub name: Option<String>,
+    ///Relative to the parent.
+    pub transform: Matrix4<f32>,
+    ///Relative to the scene root.
+    pub world_transform: Matrix4<f32>,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub camera: Option<usize>,
+}
+
+pub struct Primitive {
+    pub vertices: Vec<ModelVertex>,
+    pub indices: Vec<u32>,
+    pub material: Option<usize>,
+}
+
+pub struct MeshData {
+    pub name: Option<String>,
+    pub primitives: Vec<Primitive>,
+}
+
+///PBR metallic-roughness material, texture fields are indices into `SceneData::images`.
+pub struct MaterialData {
+    pub name: Option<String>,
+    pub base_color_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub emissive_factor: [f32; 3],
+    pub base_color_texture: Option<usize>,
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub occlusion_texture: Option<usize>,
+    pub emissive_texture: Option<usize>,
+    pub double_sided: bool,
+}
+
+///Still encoded image bytes, decoded when uploaded with `Texture::from_bytes`.
+pub struct ImageData {
+    pub name: Option<String>,
+    pub bytes: Vec<u8>,
+}
+
+pub enum ProjectionData {
+    Perspective { yfov: f32, aspect_ratio: Option<f32>, znear: f32, zfar: Option<f32> },
+    Orthographic { xmag: f32, ymag: f32, znear: f32, zfar: f32 },
+}
+
+pub struct CameraData {
+    pub name: Option<String>,
+    ///The node the camera hangs off, its world transform places the camera.
+    pub node: usize,
+    pub projection: ProjectionData,
+}
+
+pub struct SceneData {
+    pub nodes: Vec<Node>,
+    ///Top level nodes of the default scene, or the first scene when none is marked default.
+    pub roots: Vec<usize>,
+    pub meshes: Vec<MeshData>,
+    pub materials: Vec<MaterialData>,
+    pub images: Vec<ImageData>,
+    pub cameras: Vec<CameraData>,
+}
+
+impl SceneData {
+    ///Reads a `.gltf` or `.glb` file, external buffers and images are resolved next to it.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let gltf = gltf::Gltf::open(path)?;
+        Self::from_gltf(gltf, Some(path.parent().unwrap_or_else(|| Path::new(""))))
+    }
+
+    ///Reads glTF from memory, every resource has to be embedded.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        let gltf = gltf::Gltf::from_slice(bytes)?;
+        Self::from_gltf(gltf, None)
+    }
+
+    fn from_gltf(gltf: gltf::Gltf, base: Option<&Path>) -> Result<Self, Error> {
+        let gltf::Gltf { document, mut blob } = gltf;
+        let buffers = document.buffers().map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Bin => blob.take().ok_or(Error::Gltf(gltf::Error::MissingBlob)),
+            gltf::buffer::Source::Uri(uri) => read_uri(uri, base),
+        }).collect::<Result<Vec<_>, Error>>()?;
+        //Accessors trust `byteLength`, so data shorter than it would be read past its end.
+        for (buffer, data) in document.buffers().zip(&buffers) {
+            if data.len() < buffer.length() {
+                return Err(invalid(JsonPath::new().field("buffers").index(buffer.index()).field("byteLength"),
+                    ValidationError::Invalid));
+            }
+        }
+
+        let images = document.images().map(|image| {
+            let bytes = match image.source() {
+                gltf::image::Source::View { view, .. } => buffers.get(view.buffer().index())
+                    .and_then(|buffer| buffer.get(view.offset()..view.offset() + view.length()))
+                    .ok_or_else(|| invalid(JsonPath::new().field("bufferViews").index(view.index()),
+                        ValidationError::IndexOutOfBounds))?
+                    .to_vec(),
+                gltf::image::Source::Uri { uri, .. } => read_uri(uri, base)?,
+            };
+            Ok(ImageData { name: image.name().map(str::to_string), bytes })
+        }).collect::<Result<Vec<_>, Error>>()?;
+
+        let texture_image = |info: Option<gltf::texture::Info>| info.map(|i| i.texture().source().index());
+        let materials = document.materials().map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            MaterialData {
+                name: material.name().map(str::to_string),
+                base_color_factor: pbr.base_color_factor(),
+                metallic_factor: pbr.metallic_factor(),
+                roughness_factor: pbr.roughness_factor(),
+                emissive_factor: material.emissive_factor(),
+                base_color_texture: texture_image(pbr.base_color_texture()),
+                metallic_roughness_texture: texture_image(pbr.metallic_roughness_texture()),
+                normal_texture: material.normal_texture().map(|t| t.texture().source().index()),
+                occlusion_texture: material.occlusion_texture().map(|t| t.texture().source().index()),
+                emissive_texture: texture_image(material.emissive_texture()),
+                double_sided: material.double_sided(),
+            }
+        }).collect();
+
+        let meshes = document.meshes().map(|mesh| {
+            let primitives = mesh.primitives().map(|primitive| {
+                let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+                let positions: Vec<[f32; 3]> = reader.read_positions().map(|p| p.collect()).unwrap_or_default();
+                let normals: Vec<[f32; 3]> = reader.read_normals().map(|n| n.collect()).unwrap_or_default();
+                let tex_coords: Vec<[f32; 2]> = reader.read_tex_coords(0)
+                    .map(|t| t.into_f32().collect()).unwrap_or_default();
+                let indices: Vec<u32> = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect(),
+                    None => (0..positions.len() as u32).collect(),
+                };
+
+                let mut vertices: Vec<ModelVertex> = positions.iter().enumerate().map(|(i, position)| ModelVertex {
+                    position: *position,
+                    tex_coords: tex_coords.get(i).copied().unwrap_or([0.0, 0.0]),
+                    normal: normals.get(i).copied().unwrap_or([0.0, 0.0, 0.0]),
+                    tangent: [0.0; 3],
+                    bitangent: [0.0; 3],
+                }).collect();
+                if indices.iter().any(|index| *index as usize >= vertices.len()) {
+                    return Err(invalid(JsonPath::new().field("meshes").index(mesh.index())
+                        .field("primitives").index(primitive.index()).field("indices"),
+                        ValidationError::IndexOutOfBounds));
+                }
+                model::compute_tangents(&mut vertices, &indices);
+
+                Ok(Primitive { vertices, indices, material: primitive.material().index() })
+            }).collect::<Result<Vec<_>, Error>>()?;
+            Ok(MeshData { name: mesh.name().map(str::to_string), primitives })
+        }).collect::<Result<Vec<_>, Error>>()?;
+
+        let mut nodes: Vec<Node> = document.nodes().map(|node| Node {
+            name: node.name().map(str::to_string),
+            transform: node.transform().matrix().into(),
+            world_transform: Matrix4::identity(),
+            children: node.children().map(|c| c.index()).collect(),
+            mesh: node.mesh().map(|m| m.index()),
+            camera: node.camera().map(|c| c.index()),
+        }).collect();
+
+        let roots: Vec<usize> = match document.default_scene().or_else(|| document.scenes().next()) {
+            Some(scene) => scene.nodes().map(|n| n.index()).collect(),
+            None => Vec::new(),
+        };
+        //Nodes form trees, one reached twice has two parents or is its own ancestor.
+        let mut visited = vec![false; nodes.len()];
+        let mut stack: Vec<(usize, Matrix4<f32>)> = roots.iter().map(|r| (*r, Matrix4::identity())).collect();
+        while let Some((index, parent)) = stack.pop() {
+            let path = || JsonPath::new().field("nodes").index(index);
+            let node = nodes.get_mut(index).ok_or_else(|| invalid(path(), ValidationError::IndexOutOfBounds))?;
+            if std::mem::replace(&mut visited[index], true) {
+                return Err(invalid(path(), ValidationError::Invalid));
+            }
+            let world = parent * node.transform;
+            node.world_transform = world;
+            stack.extend(node.children.iter().map(|child| (*child, world)));
+        }
+
+        let mut cameras: Vec<CameraData> = Vec::new();
+        for camera in document.cameras() {
+            let projection = match camera.projection() {
+                gltf::camera::Projection::Perspective(p) => ProjectionData::Perspective {
+                    yfov: p.yfov(),
+                    aspect_ratio: p.aspect_ratio(),
+                    znear: p.znear(),
+                    zfar: p.zfar(),
+                },
+                gltf::camera::Projection::Orthographic(o) => ProjectionData::Orthographic {
+                    xmag: o.xmag(),
+                    ymag: o.ymag(),
+                    znear: o.znear(),
+                    zfar: o.zfar(),
+                },
+            };
+            //A camera nothing points at can't be placed, so it is left out.
+            if let Some(node) = nodes.iter().position(|n| n.camera == Some(camera.index())) {
+                cameras.push(CameraData { name: camera.name().map(str::to_string), node, projection });
+            }
+        }
+
+        Ok(Self { nodes, roots, meshes, materials, images, cameras })
+    }
+
+    ///Places a `camera::Camera` where the glTF camera is, looking down its -Z axis, with a matching projection.
+    pub fn camera(&self, index: usize, width: u32, height: u32)
+        -> Option<(camera::Camera, Box<dyn camera::CameraProjection>)>
+    {
+        let data = self.cameras.get(index)?;
+        let world = self.nodes[data.node].world_transform;
+        let position = world.w.truncate();
+        let forward = (world * Vector4::new(0.0, 0.0, -1.0, 0.0)).truncate().normalize();
+        let yaw = cgmath::Rad(forward.z.atan2(forward.x));
+        let pitch = cgmath::Rad(forward.y.asin());
+
+        let projection: Box<dyn camera::CameraProjection> = match data.projection {
+            ProjectionData::Perspective { yfov, znear, zfar, .. } => Box::new(
+                camera::Projection::new(width, height, cgmath::Rad(yfov), znear, zfar.unwrap_or(DEFAULT_ZFAR))
+            ),
+            //glTF gives half the height, the width comes from the window instead of xmag.
+            ProjectionData::Orthographic { ymag, znear, zfar, .. } => Box::new(
+                camera::OrthographicProjection::new(width, height, camera::OrthographicMode::FixedHeight(ymag * 2.0),
+                    znear, zfar)
+            ),
+        };
+        Some((camera::Camera::new(cgmath::Point3::new(position.x, position.y, position.z), yaw, pitch), projection))
+    }
+}
+
+fn invalid(path: JsonPath, error: ValidationError) -> Error {
+    Error::Gltf(gltf::Error::Validation(vec![(path, error)]))
+}
+
+fn read_uri(uri: &str, base: Option<&Path>) -> Result<Vec<u8>, Error> {
+    if uri.starts_with("data:") {
+        let encoded = uri.split_once(',').map_or("", |(_, encoded)| encoded);
+        return base64::decode(encoded).map_err(|e| Error::Gltf(gltf::Error::Base64(e)));
+    }
+    match base {
+        Some(base) => std::fs::read(base.join(uri)).map_err(|e| Error::Gltf(gltf::Error::Io(e))),
+        None => Err(Error::Gltf(gltf::Error::ExternalReferenceInSliceImport)),
+    }
+}
+
+///A `SceneData` uploaded to the gpu, every primitive is one mesh of `model`.
+pub struct GpuScene {
+    pub model: Model,
+    ///The range of `model.meshes` holding each glTF mesh's primitives.
+    pub meshes: Vec<Range<usize>>,
+}
+
+impl GpuScene {
+    ///Materials bind base color and normal maps with `Texture::build_texture_bind_group_layout`,
+    ///missing maps fall back to the base color factor and a flat normal.
+    pub fn upload(scene: &SceneData, device: &wgpu::Device, queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout) -> Result<Self, Error>
+    {
+        let mut materials = Vec::new();
+        for (i, material) in scene.materials.iter().chain(std::iter::once(&default_material())).enumerate() {
+            let name = material.name.clone().unwrap_or_else(|| format!("material-{}", i));
+            let diffuse_texture = match material.base_color_texture {
+                Some(image) => texture::Texture::from_bytes_with_mipmaps(device, queue, &scene.images[image].bytes, &name, false)?,
+                None => {
+                    let c = material.base_color_factor;
+                    let color = [(c[0] * 255.0) as u8, (c[1] * 255.0) as u8, (c[2] * 255.0) as u8, (c[3] * 255.0) as u8];
+                    model::solid_texture(device, queue, color, &name, false)?
+                }
+            };
+            let normal_texture = match material.normal_texture {
+                Some(image) => texture::Texture::from_bytes_with_mipmaps(device, queue, &scene.images[image].bytes, &name, true)?,
+                None => model::solid_texture(device, queue, [128, 128, 255, 255], &name, true)?,
+            };
+            materials.push(Material::new(device, &name, diffuse_texture, normal_texture, layout));
+        }
+        //Primitives without a material use the default one pushed last.
+        let default = materials.len() - 1;
+
+        let mut model_meshes = Vec::new();
+        let mut ranges = Vec::new();
+        for mesh in &scene.meshes {
+            let start = model_meshes.len();
+            for (i, primitive) in mesh.primitives.iter().enumerate() {
+                let name = format!("{}-{}", mesh.name.as_deref().unwrap_or("mesh"), i);
+                model_meshes.push(ModelMesh {
+                    mesh: Mesh::new(device, &name, &primitive.vertices, Some(Indices::U32(&primitive.indices))),
+                    material: primitive.material.unwrap_or(default),
+                });
+            }
+            ranges.push(start..model_meshes.len());
+        }
+
+        Ok(Self {
+            model: Model { meshes: model_meshes, materials },
+            meshes: ranges,
+        })
+    }
+}
+
+fn default_material() -> MaterialData {
+    MaterialData {
+        name: Some("default".to_string()),
+        base_color_factor: [1.0, 1.0, 1.0, 1.0],
+        metallic_factor: 1.0,
+        roughness_factor: 1.0,
+        emissive_factor: [0.0, 0.0, 0.0],
+        base_color_texture: None,
+        metallic_roughness_texture: None,
+        normal_texture: None,
+        occlusion_texture: None,
+        emissive_texture: None,
+        double_sided: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_scene_structure() {
+        let scene = SceneData::load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/assets/scene.gltf")).unwrap();
+
+        assert_eq!(scene.roots, vec![0]);
+        assert_eq!(scene.nodes.len(), 3);
+        assert_eq!(scene.nodes[0].children, vec![1, 2]);
+        assert_eq!(scene.nodes[1].mesh, Some(0));
+
+        let mesh = &scene.meshes[0];
+        assert_eq!(mesh.primitives.len(), 2);
+        assert_eq!(mesh.primitives[0].indices, vec![0, 1, 2]);
+        assert_eq!(mesh.primitives[0].vertices[1].position, [1.0, 0.0, 0.0]);
+        assert_eq!(mesh.primitives[1].material, Some(1));
+
+        let red = &scene.materials[0];
+        assert_eq!(red.base_color_factor, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(red.metallic_factor, 0.5);
+        assert_eq!(red.roughness_factor, 0.25);
+        let image = image::load_from_memory(&scene.images[red.base_color_texture.unwrap()].bytes).unwrap();
+        assert_eq!(image::GenericImageView::dimensions(&image), (1, 1));
+
+        let (camera, _) = scene.camera(0, 800, 600).unwrap();
+        assert_eq!(camera.position, cgmath::Point3::new(1.0, 0.0, 5.0));
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let cyclic = r#"{"asset": {"version": "2.0"}, "scene": 0, "scenes": [{"nodes": [0]}],
+            "nodes": [{"children": [1]}, {"children": [0]}]}"#;
+        match SceneData::from_slice(cyclic.as_bytes()) {
+            Err(Error::Gltf(gltf::Error::Validation(errors))) => assert_eq!(errors[0].0.as_str(), "nodes[0]"),
+            other => panic!("expected a validation error, got {:?}", other.map(|_| ())),
+        }
+
+        let rejected_at = |json: &str, path: &str| match SceneData::from_slice(json.as_bytes()) {
+            Err(Error::Gltf(gltf::Error::Validation(errors))) => assert_eq!(errors[0].0.as_str(), path),
+            other => panic!("expected a validation error at {}, got {:?}", path, other.map(|_| ())),
+        };
+
+        //The image's view runs past the end of its buffer.
+        rejected_at(r#"{"asset": {"version": "2.0"},
+            "buffers": [{"byteLength": 4, "uri": "data:application/octet-stream;base64,AAAAAA=="}],
+            "bufferViews": [{"buffer": 0, "byteLength": 64}],
+            "images": [{"bufferView": 0, "mimeType": "image/png"}]}"#, "bufferViews[0]");
+
+        //The buffer claims more bytes than its data holds, and positions are read from the missing part.
+        rejected_at(r#"{"asset": {"version": "2.0"},
+            "buffers": [{"byteLength": 36, "uri": "data:application/octet-stream;base64,AAAAAA=="}],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}],
+            "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0, 0, 0], "max": [1, 1, 0]}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}]}"#, "buffers[0].byteLength");
+
+        //A triangle of three vertices pointing at a sixth.
+        rejected_at(r#"{"asset": {"version": "2.0"},
+            "buffers": [{"byteLength": 44,
+                "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAUAAAA="}],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}, {"buffer": 0, "byteOffset": 36, "byteLength": 6}],
+            "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0, 0, 0], "max": [1, 1, 0]},
+                {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]}]}"#,
+            "meshes[0].primitives[0].indices");
+    }
+}
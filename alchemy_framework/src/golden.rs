@@ -0,0 +1,209 @@
+//!Golden image testing, render a few frames headlessly and compare the result against a stored PNG.
+use crate::gpu;
+use image::{Rgba, RgbaImage};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+#[allow(unused_imports)]
+use log::{error, warn, info, debug, trace};
+
+///Setting this environment variable writes every reference image instead of comparing against it,
+///the only way a missing reference gets created.
+pub const BLESS_VAR: &str = "ALCHEMY_BLESS";
+
+#[derive(Debug)]
+pub enum GoldenError {
+    ///There is no reference image yet, run with `ALCHEMY_BLESS` set to write it.
+    MissingReference(PathBuf),
+    ///The captured frame and the reference are not the same size.
+    SizeMismatch { actual: (u32, u32), expected: (u32, u32) },
+    ///More pixels than allowed were outside the tolerance, a diff image was written to `diff`.
+    Mismatch { pixels: usize, diff: PathBuf },
+    ///Rendering or reading the frame back from the gpu failed.
+    Gpu(crate::Error),
+    Image(image::ImageError),
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenError::MissingReference(path) => write!(f, "no reference image at {}, set {} to write it",
+                path.display(), BLESS_VAR),
+            GoldenError::SizeMismatch { actual, expected } => write!(f, "frame is {}x{} but the reference is {}x{}",
+                actual.0, actual.1, expected.0, expected.1),
+            GoldenError::Mismatch { pixels, diff } => write!(f, "{} pixels differ from the reference, see {}",
+                pixels, diff.display()),
+            GoldenError::Gpu(e) => write!(f, "failed to render the frame: {}", e),
+            GoldenError::Image(e) => write!(f, "failed to read or write an image: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+impl From<image::ImageError> for GoldenError {
+    fn from(e: image::ImageError) -> Self {
+        GoldenError::Image(e)
+    }
+}
+
+impl From<crate::Error> for GoldenError {
+    fn from(e: crate::Error) -> Self {
+        GoldenError::Gpu(e)
+    }
+}
+
+///How a golden test is run and how strict the comparison is.
+#[derive(Debug, Clone)]
+pub struct Golden {
+    pub frames: u32,
+    pub delta: Duration,
+    ///Largest per channel difference that still counts as the same pixel.
+    pub tolerance: u8,
+    ///How many pixels may exceed the tolerance before the comparison fails.
+    pub max_failing_pixels: usize,
+}
+
+impl Golden {
+    pub fn new(frames: u32, delta: Duration, tolerance: u8) -> Self {
+        Self {
+            frames,
+            delta,
+            tolerance,
+            max_failing_pixels: 0,
+        }
+    }
+
+    ///Calls `update` then renders, once per frame with the fixed delta, and captures the last frame.
+    pub fn run<F>(&self, gpu: &mut gpu::State, mut update: F) -> Result<RgbaImage, GoldenError>
+    where
+        F: FnMut(&mut gpu::State, Duration),
+    {
+        for _ in 0..self.frames {
+            update(gpu, self.delta);
+            gpu.render()?;
+        }
+        Ok(gpu.capture_frame()?)
+    }
+
+    ///Runs the frames and compares the capture against the PNG at `reference`.
+    pub fn check<F, P>(&self, gpu: &mut gpu::State, reference: P, update: F) -> Result<(), GoldenError>
+    where
+        F: FnMut(&mut gpu::State, Duration),
+        P: AsRef<Path>,
+    {
+        let frame = self.run(gpu, update)?;
+        self.compare(&frame, reference)
+    }
+
+    ///Compares `actual` against the PNG at `reference`, writing a diff image next to it on failure.
+    ///While `ALCHEMY_BLESS` is set the reference is written out from `actual` instead,
+    ///otherwise a missing reference is an error.
+    pub fn compare<P: AsRef<Path>>(&self, actual: &RgbaImage, reference: P) -> Result<(), GoldenError> {
+        let reference = reference.as_ref();
+        if std::env::var_os(BLESS_VAR).is_some() {
+            warn!("Writing golden reference {}", reference.display());
+            if let Some(parent) = reference.parent() {
+                std::fs::create_dir_all(parent).map_err(image::ImageError::IoError)?;
+            }
+            actual.save(reference)?;
+            return Ok(());
+        }
+        if !reference.exists() {
+            return Err(GoldenError::MissingReference(reference.to_path_buf()));
+        }
+
+        let expected = image::open(reference)?.to_rgba8();
+        if expected.dimensions() != actual.dimensions() {
+            return Err(GoldenError::SizeMismatch { actual: actual.dimensions(), expected: expected.dimensions() });
+        }
+
+        let (diff, pixels) = diff_images(actual, &expected, self.tolerance);
+        if pixels <= self.max_failing_pixels {
+            return Ok(());
+        }
+
+        let diff_path = reference.with_extension("diff.png");
+        diff.save(&diff_path)?;
+        actual.save(reference.with_extension("actual.png"))?;
+        Err(GoldenError::Mismatch { pixels, diff: diff_path })
+    }
+}
+
+///Marks every pixel outside the tolerance in red over a dimmed copy of `actual`, returns the image and how many failed.
+pub fn diff_images(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> (RgbaImage, usize) {
+    let mut failing = 0;
+    let diff = RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let a = actual.get_pixel(x, y);
+        let e = expected.get_pixel(x, y);
+        let within = a.0.iter().zip(e.0.iter())
+            .all(|(a, e)| (*a as i16 - *e as i16).abs() <= tolerance as i16);
+        if within {
+            let luma = ((a[0] as u16 + a[1] as u16 + a[2] as u16) / 12) as u8;
+            Rgba([luma, luma, luma, 255])
+        } else {
+            failing += 1;
+            Rgba([255, 0, 0, 255])
+        }
+    });
+    (diff, failing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_respects_tolerance() {
+        let expected = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255]));
+        let mut actual = expected.clone();
+        actual.put_pixel(0, 0, Rgba([103, 100, 100, 255]));
+        actual.put_pixel(1, 0, Rgba([110, 100, 100, 255]));
+
+        let (_, failing) = diff_images(&actual, &expected, 3);
+        assert_eq!(failing, 1);
+        let (diff, failing) = diff_images(&actual, &expected, 0);
+        assert_eq!(failing, 2);
+        assert_eq!(*diff.get_pixel(1, 0), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn missing_reference_fails() {
+        let frame = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 255]));
+        let reference = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/does_not_exist.png");
+        match Golden::new(1, Duration::from_millis(16), 0).compare(&frame, reference) {
+            Err(GoldenError::MissingReference(path)) => assert_eq!(path, Path::new(reference)),
+            other => panic!("expected a missing reference, got {:?}", other),
+        }
+        assert!(!Path::new(reference).exists());
+    }
+
+    #[test]
+    fn basic_effect_matches_reference() {
+        use futures::executor::block_on;
+        //Machines without any adapter, software ones included, can't say anything about rendering.
+        let mut gpu = match block_on(gpu::State::new_headless(128, 128, &gpu::Settings::headless())) {
+            Ok(gpu) => gpu,
+            Err(crate::Error::NoAdapter) => {
+                eprintln!("skipping basic_effect_matches_reference, no graphics adapter");
+                return;
+            }
+            Err(e) => panic!("couldn't create a headless state: {}", e),
+        };
+
+        use crate::camera::CameraControl;
+        let mut camera = crate::camera::CameraObject::new(&gpu.sc_desc);
+        camera.update();
+        let camera_obj = crate::camera::GPUObject::from_uniform(&gpu.device, camera.uniforms, 0, "camera");
+        let effect = gpu::BasicEffect::new(&gpu, camera_obj);
+        gpu.add_effect(effect);
+
+        let golden = Golden::new(3, Duration::from_millis(16), 2);
+        let reference = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/basic_effect.png");
+        golden.check(&mut gpu, reference, |gpu, delta| {
+            camera.controller.update_camera(&mut camera.camera, delta);
+            camera.update();
+            gpu.get_effect::<gpu::BasicEffect>().unwrap().write_camera_buffer(gpu, camera.uniforms);
+        }).unwrap();
+    }
+}
@@ -1,197 +1,640 @@
 use crate::camera::GPUObject;
+use crate::graph::{Effect, EffectId, FrameTargets, RenderGraph, SortKey, MAIN_PASS};
+use crate::shader::{Shader, ShaderCache};
+use crate::skybox::Skybox;
+use crate::mesh::{Mesh, Vertex};
+use crate::pipeline::{PipelineBuilder, PipelineCache};
 use crate::texture;
+use crate::Error;
 use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use winit::window::Window;
 #[allow(unused_imports)]
 use log::{error, warn, info, debug, trace};
 
 const RENDERFORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
 const SCUSAGE: wgpu::TextureUsage = wgpu::TextureUsage::RENDER_ATTACHMENT;
-const SCPRESENT: wgpu::PresentMode = wgpu::PresentMode::Fifo;
+const OFFSCREENFORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vsync {
+    ///Wait for vertical blank, never tears.
+    On,
+    ///Present immediately, may tear.
+    Off,
+    ///Replace the queued frame with the newest one, doesn't tear or block.
+    Mailbox,
+}
+
+impl Vsync {
+    pub fn present_mode(self) -> wgpu::PresentMode {
+        match self {
+            Vsync::On => wgpu::PresentMode::Fifo,
+            Vsync::Off => wgpu::PresentMode::Immediate,
+            Vsync::Mailbox => wgpu::PresentMode::Mailbox,
+        }
+    }
+}
+
+///Everything used to pick the adapter, device and surface, defaults match what the framework always did.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub vsync: Vsync,
+    pub backends: wgpu::BackendBit,
+    pub power_preference: wgpu::PowerPreference,
+    ///Format of the frame, `None` asks the adapter for the surface's preferred format.
+    pub format: Option<wgpu::TextureFormat>,
+    pub features: wgpu::Features,
+    pub limits: wgpu::Limits,
+    ///Format of the main depth buffer, `Depth24PlusStencil8` gives it a stencil buffer too.
+    pub depth_format: wgpu::TextureFormat,
+    ///Samples per pixel of the frame and depth buffer, 1 turns multisampling off and 4 works everywhere.
+    ///Pipelines from `PipelineBuilder` match it, ones drawing into graph textures need `with_sample_count(1)`.
+    pub sample_count: u32,
+    ///Watches shader files loaded through `State::shaders` and rebuilds effects using them when they change.
+    pub hot_reload: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            vsync: Vsync::On,
+            backends: wgpu::BackendBit::VULKAN,
+            power_preference: wgpu::PowerPreference::default(),
+            format: Some(RENDERFORMAT),
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::default(),
+            depth_format: texture::Texture::DEPTH_FORMAT,
+            sample_count: 1,
+            hot_reload: false,
+        }
+    }
+}
+
+impl Settings {
+    ///The defaults with any primary backend allowed, for `State::new_headless` on machines without Vulkan.
+    pub fn headless() -> Self {
+        Self {
+            backends: wgpu::BackendBit::PRIMARY,
+            ..Self::default()
+        }
+    }
+}
+
+pub const DEFAULT_CLEAR_COLOR: wgpu::Color = wgpu::Color { r: 0.1, g: 0.2, b: 0.3, a: 1.0 };
+
+///What the main pass starts from each frame.
+pub enum Background {
+    Color(wgpu::Color),
+    ///Keeps what's already in the frame, for drawing over an earlier pass.
+    Keep,
+    ///Draws a cube map behind everything else in the main pass.
+    Skybox(Skybox),
+}
+
+///How the main pass clears the frame and depth buffer, read every frame so it can change at any time.
+pub struct FrameSettings {
+    pub background: Background,
+    ///`None` keeps the depth from an earlier pass.
+    pub depth_clear: Option<f32>,
+    ///Only used when `Settings::depth_format` has a stencil aspect, `None` keeps the stencil.
+    pub stencil_clear: Option<u32>,
+}
+
+impl Default for FrameSettings {
+    fn default() -> Self {
+        Self {
+            background: Background::Color(DEFAULT_CLEAR_COLOR),
+            depth_clear: Some(1.0),
+            stencil_clear: Some(0),
+        }
+    }
+}
+
+impl FrameSettings {
+    pub fn with_clear_color(mut self, color: wgpu::Color) -> Self {
+        self.background = Background::Color(color);
+        self
+    }
+
+    pub fn with_skybox(mut self, skybox: Skybox) -> Self {
+        self.background = Background::Skybox(skybox);
+        self
+    }
+
+    pub(crate) fn color_load(&self) -> wgpu::LoadOp<wgpu::Color> {
+        match self.background {
+            Background::Color(color) => wgpu::LoadOp::Clear(color),
+            Background::Keep => wgpu::LoadOp::Load,
+            Background::Skybox(_) => wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+        }
+    }
+
+    pub(crate) fn depth_load(&self) -> wgpu::LoadOp<f32> {
+        self.depth_clear.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear)
+    }
+
+    pub(crate) fn stencil_load(&self) -> wgpu::LoadOp<u32> {
+        self.stencil_clear.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear)
+    }
+
+    pub fn skybox(&self) -> Option<&Skybox> {
+        match &self.background {
+            Background::Skybox(skybox) => Some(skybox),
+            _ => None,
+        }
+    }
+}
+
+///Where the frame ends up, either a window's swap chain or a texture nobody presents.
+enum Target {
+    Window {
+        surface: wgpu::Surface,
+        swap_chain: wgpu::SwapChain,
+    },
+    Offscreen {
+        color: texture::Texture,
+    },
+}
 
 pub struct State {
-    surface: wgpu::Surface,
+    target: Target,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub sc_desc: wgpu::SwapChainDescriptor,
-    swap_chain: wgpu::SwapChain,
     pub size: winit::dpi::PhysicalSize<u32>,
+    pub settings: Settings,
     depth_texture: texture::Texture,
-    effect: Option<BasicEffect>, //This is initialized later.
+    ///Drawn into in place of the frame when multisampling, then resolved into it.
+    multisampled: Option<texture::Texture>,
+    ///The passes drawn each frame, starting as one main pass into the frame.
+    pub graph: RenderGraph,
+    ///Clears of the main pass, change them between frames.
+    pub frame: FrameSettings,
+    ///Shader modules loaded from files, watched for changes when `Settings::hot_reload` is on.
+    pub shaders: ShaderCache,
+    ///Pipelines shared between effects built from the same `PipelineBuilder`.
+    pub pipelines: PipelineCache,
 }
 
 impl State {
-    pub async fn new(window: &Window) -> Self {
+    pub async fn new(window: &Window, settings: &Settings) -> Result<Self, Error> {
         let size = window.inner_size();
-        let instance = wgpu::Instance::new(wgpu::BackendBit::VULKAN);
+        let instance = wgpu::Instance::new(settings.backends);
         let surface = unsafe { instance.create_surface(window) };
         let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
+            power_preference: settings.power_preference,
             compatible_surface: Some(&surface),
-        }).await.unwrap();
+        }).await.ok_or(Error::NoAdapter)?;
 
         let (device, queue) = adapter.request_device(
             &wgpu::DeviceDescriptor {
                 label: Some("Named Device"),
-                features: wgpu::Features::empty(),
-                limits: wgpu::Limits::default(),
+                features: settings.features,
+                limits: settings.limits.clone(),
             },
             None, // Trace path
-        ).await.unwrap();
+        ).await?;
 
-        let sc_desc = wgpu::SwapChainDescriptor { usage: SCUSAGE, format: RENDERFORMAT, width: size.width, height: size.height, present_mode: SCPRESENT};
+        let format = match settings.format {
+            Some(format) => format,
+            None => adapter.get_swap_chain_preferred_format(&surface),
+        };
+        let sc_desc = wgpu::SwapChainDescriptor { usage: SCUSAGE, format, width: size.width, height: size.height, present_mode: settings.vsync.present_mode()};
 
         let swap_chain = device.create_swap_chain(&surface, &sc_desc);
-        let depth_texture = texture::Texture::create_depth_texture(&device, &sc_desc, "depth_texture");
+        let (depth_texture, multisampled) = Self::create_frame_buffers(&device, &sc_desc, settings);
 
-        Self {
-            surface,
+        Ok(Self {
+            target: Target::Window { surface, swap_chain },
+            device,
+            queue,
+            sc_desc,
+            size,
+            settings: settings.clone(),
+            depth_texture,
+            multisampled,
+            graph: RenderGraph::with_main_pass(),
+            frame: FrameSettings::default(),
+            shaders: ShaderCache::new(settings.hot_reload)?,
+            pipelines: PipelineCache::new(),
+        })
+    }
+
+    ///Creates a state that renders into an offscreen color and depth target instead of a window.
+    ///Takes whatever adapter `settings.backends` offers, software drivers included, so with
+    ///`Settings::headless()` it runs in CI on any platform.
+    pub async fn new_headless(width: u32, height: u32, settings: &Settings) -> Result<Self, Error> {
+        let size = winit::dpi::PhysicalSize::new(width, height);
+        let instance = wgpu::Instance::new(settings.backends);
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: settings.power_preference,
+            compatible_surface: None,
+        }).await.ok_or(Error::NoAdapter)?;
+
+        let (device, queue) = adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("Headless Device"),
+                features: settings.features,
+                limits: settings.limits.clone(),
+            },
+            None, // Trace path
+        ).await?;
+
+        //Nothing is ever presented, the descriptor just keeps size and format in one place like the windowed path.
+        let format = settings.format.unwrap_or(OFFSCREENFORMAT);
+        let sc_desc = wgpu::SwapChainDescriptor { usage: SCUSAGE | wgpu::TextureUsage::COPY_SRC, format, width, height, present_mode: settings.vsync.present_mode()};
+
+        let color = texture::Texture::create_render_target(&device, &sc_desc, "offscreen_texture");
+        let (depth_texture, multisampled) = Self::create_frame_buffers(&device, &sc_desc, settings);
+
+        Ok(Self {
+            target: Target::Offscreen { color },
             device,
             queue,
             sc_desc,
-            swap_chain,
             size,
+            settings: settings.clone(),
             depth_texture,
-            effect: None,
+            multisampled,
+            graph: RenderGraph::with_main_pass(),
+            frame: FrameSettings::default(),
+            shaders: ShaderCache::new(settings.hot_reload)?,
+            pipelines: PipelineCache::new(),
+        })
+    }
+
+    ///The depth buffer and, when multisampling, the color target resolved into the frame.
+    fn create_frame_buffers(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor, settings: &Settings)
+        -> (texture::Texture, Option<texture::Texture>)
+    {
+        let depth_texture = texture::Texture::create_depth_texture_with_format(device, sc_desc, settings.depth_format,
+            settings.sample_count, "depth_texture");
+        let multisampled = if settings.sample_count > 1 {
+            let mut desc = sc_desc.clone();
+            desc.usage = wgpu::TextureUsage::RENDER_ATTACHMENT;
+            Some(texture::Texture::create_multisampled_target(device, &desc, settings.sample_count, "multisampled_frame"))
+        } else {
+            None
+        };
+        (depth_texture, multisampled)
+    }
+
+    pub fn is_headless(&self) -> bool {
+        match self.target {
+            Target::Offscreen { .. } => true,
+            Target::Window { .. } => false,
+        }
+    }
+
+    ///Adds an effect to the main pass, putting the main pass back if it was removed.
+    ///Every effect added is drawn, ordered by its `Effect::sort_key`.
+    pub fn add_effect<E: Effect>(&mut self, effect: E) -> EffectId {
+        if self.graph.pass(MAIN_PASS).is_none() {
+            let main = RenderGraph::with_main_pass().remove_pass(MAIN_PASS).expect("main pass");
+            self.graph.add_pass(main);
         }
+        return self.graph.pass_mut(MAIN_PASS).expect("main pass").add_effect(effect);
     }
 
-    pub fn add_effect(&mut self, effect: BasicEffect){ 
-        self.effect = Some(effect);
+    pub fn effect<E: Effect>(&self, id: EffectId) -> Result<&E, Error>{
+        return self.graph.effect::<E>(id).ok_or(Error::MissingEffect);
     }
 
-    pub fn get_effect(&self) -> &BasicEffect{
-        return self.effect.as_ref().unwrap();
+    pub fn effect_mut<E: Effect>(&mut self, id: EffectId) -> Result<&mut E, Error>{
+        return self.graph.effect_mut::<E>(id).ok_or(Error::MissingEffect);
+    }
+
+    pub fn remove_effect(&mut self, id: EffectId) -> Option<Box<dyn Effect>> {
+        self.graph.remove_effect(id)
+    }
+
+    ///The first effect of type `E` in any pass, handy when there's only one.
+    pub fn get_effect<E: Effect>(&self) -> Result<&E, Error>{
+        return self.graph.find_effect::<E>().ok_or(Error::MissingEffect);
+    }
+
+    pub fn get_effect_mut<E: Effect>(&mut self) -> Result<&mut E, Error>{
+        return self.graph.find_effect_mut::<E>().ok_or(Error::MissingEffect);
+    }
+
+    fn has_effects(&self) -> bool {
+        self.graph.passes().iter().any(|pass| !pass.effects.is_empty())
+    }
+
+    ///Recompiles changed shader files and lets every effect rebuild its pipelines, called between frames.
+    ///A shader that fails to compile logs why and the effects keep drawing with the old one.
+    pub fn reload_shaders(&mut self) {
+        if !self.shaders.is_watching() {
+            return;
+        }
+        let changed = self.shaders.poll(&self.device);
+        if changed.is_empty() {
+            return;
+        }
+        //Reloaded modules can take the place of the old ones, so cached pipelines can't be trusted.
+        self.pipelines.clear();
+        let mut graph = std::mem::take(&mut self.graph);
+        graph.reload(self, &changed);
+        self.graph = graph;
+    }
+
+    ///Switches presentation mode at runtime, rebuilding the swap chain.
+    pub fn set_vsync(&mut self, vsync: Vsync) {
+        self.settings.vsync = vsync;
+        self.sc_desc.present_mode = vsync.present_mode();
+        self.resize(self.size);
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.size = new_size;
         self.sc_desc.width = new_size.width;
         self.sc_desc.height = new_size.height;
-        self.swap_chain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
-        self.depth_texture = texture::Texture::create_depth_texture(&self.device, &self.sc_desc, "depth_texture");
+        match &mut self.target {
+            Target::Window { surface, swap_chain } => {
+                *swap_chain = self.device.create_swap_chain(surface, &self.sc_desc);
+            }
+            Target::Offscreen { color } => {
+                *color = texture::Texture::create_render_target(&self.device, &self.sc_desc, "offscreen_texture");
+            }
+        }
+        let (depth_texture, multisampled) = Self::create_frame_buffers(&self.device, &self.sc_desc, &self.settings);
+        self.depth_texture = depth_texture;
+        self.multisampled = multisampled;
+        self.graph.resize(&self.device, &self.sc_desc);
     }
 
     pub fn write_buffer(&mut self, buffer: &wgpu::Buffer, bytes: impl bytemuck::Pod ){
         self.queue.write_buffer(&buffer, 0, bytemuck::cast_slice(&[bytes]));
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SwapChainError> {
-        let frame = self.swap_chain.get_current_frame()?.output;
+    pub fn render(&mut self) -> Result<(), Error> {
+        if !self.has_effects() {
+            return Err(Error::MissingEffect);
+        }
+
+        //The swap chain frame has to stay alive until the work is submitted.
+        let frame = match &self.target {
+            Target::Window { swap_chain, .. } => Some(swap_chain.get_current_frame()?.output),
+            Target::Offscreen { .. } => None,
+        };
+        let view = match (&frame, &self.target) {
+            (Some(frame), _) => &frame.view,
+            (None, Target::Offscreen { color }) => &color.view,
+            (None, Target::Window { .. }) => unreachable!(),
+        };
 
         let mut encoder = self.device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Render Encoder"),});
 
-        {
-            let mut render_pass = encoder
-            .begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                    attachment: &frame.view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
-                        store: true,
-                    },
-                }],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
-                    attachment: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: true,
-                    }),
-                    stencil_ops: None,
-                }),
-            });
-            
-            match &self.effect{
-                Some(effect) => {
-                    effect.render(&mut render_pass);
+        self.graph.record(&mut encoder, &self.frame_targets(view))?;
+
+        self.queue.submit(iter::once(encoder.finish()));
+
+        Ok(())
+    }
+
+    fn frame_targets<'a>(&'a self, frame: &'a wgpu::TextureView) -> FrameTargets<'a> {
+        FrameTargets {
+            frame,
+            multisampled: self.multisampled.as_ref().map(|texture| &texture.view),
+            depth: &self.depth_texture.view,
+            depth_format: self.settings.depth_format,
+            settings: &self.frame,
+        }
+    }
+
+    ///Draws the current frame into a copyable texture and reads it back as RGBA.
+    ///
+    ///A windowed state does not copy what was presented: swap chain images can only be render attachments,
+    ///so the whole graph is recorded again into a scratch texture. Anything changed since the last `render`,
+    ///like buffers written in between, shows up in the capture even though it was never on screen.
+    ///Only 8 bit RGBA and BGRA frame formats can be captured, others return `Error::UnsupportedFormat`.
+    pub fn capture_frame(&self) -> Result<image::RgbaImage, Error> {
+        if !self.has_effects() {
+            return Err(Error::MissingEffect);
+        }
+        let bgra = match self.sc_desc.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => return Err(Error::UnsupportedFormat(format)),
+        };
+
+        let scratch;
+        let color = match &self.target {
+            Target::Offscreen { color } => color,
+            Target::Window { .. } => {
+                let mut desc = self.sc_desc.clone();
+                desc.usage = SCUSAGE | wgpu::TextureUsage::COPY_SRC;
+                scratch = texture::Texture::create_render_target(&self.device, &desc, "capture_texture");
+                &scratch
+            }
+        };
+
+        let width = self.sc_desc.width;
+        let height = self.sc_desc.height;
+        let unpadded_bytes_per_row = 4 * width;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Capture Encoder"),});
+
+        self.graph.record(&mut encoder, &self.frame_targets(&color.view))?;
+
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture: &color.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::BufferCopyView {
+                buffer: &buffer,
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: padded_bytes_per_row,
+                    rows_per_image: height,
                 },
-                None => panic!("The Render pipeline was not initialized, please include init_pipleine somehwere in the code"),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+        );
+
+        self.queue.submit(iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+        futures::executor::block_on(mapping)?;
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
             }
         }
+        buffer.unmap();
 
-        self.queue.submit(iter::once(encoder.finish()));
+        if bgra {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
 
+        Ok(image::RgbaImage::from_raw(width, height, pixels).expect("capture buffer matches the frame size"))
+    }
+
+    ///Captures the current frame and writes it out, the image format follows the file extension.
+    pub fn save_frame<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), Error> {
+        let frame = self.capture_frame()?;
+        frame.save(path)?;
         Ok(())
     }
 }
 
 pub struct BasicEffect {
-    pub render_pipeline: wgpu::RenderPipeline,
+    ///Shared through `State::pipelines` with other effects using the same built in or file shaders.
+    pub render_pipeline: Arc<wgpu::RenderPipeline>,
     pub camera_obj: GPUObject<crate::camera::Uniforms>,
+    ///Drawn in order when present, otherwise the shader's built in triangle is drawn if it has one.
+    pub meshes: Vec<Mesh>,
+    pub sort_key: SortKey,
+    ///Whether the pipeline reads no vertex buffers, so the vertex shader makes up a triangle on its own.
+    draws_triangle: bool,
+    ///Where the shaders came from when built with `from_files`, so the pipeline can be rebuilt on reload.
+    files: Option<ShaderFiles>,
+}
+
+struct ShaderFiles {
+    vertex: (PathBuf, String),
+    fragment: (PathBuf, String),
+    buffers: Vec<wgpu::VertexBufferLayout<'static>>,
 }
 
 impl BasicEffect {
     pub fn new(gpu: &State, camera_obj: GPUObject<crate::camera::Uniforms>) -> Self{
-        let vs_module = gpu.device.create_shader_module(&wgpu::include_spirv!("shader.vert.spv"));
-        let fs_module = gpu.device.create_shader_module(&wgpu::include_spirv!("shader.frag.spv"));
-
-        let render_pipeline_layout =
-        gpu.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&camera_obj.layout],
-            push_constant_ranges: &[],
-        });
+        let module = Shader::wgsl("basic.wgsl", include_str!("basic.wgsl")).create_module(&gpu.device)
+            .expect("built in shader validates");
+
+        let render_pipeline = Self::create_pipeline(gpu, &camera_obj, (&module, "vs_main"), (&module, "fs_main"), &[],
+            Some("basic.wgsl"));
 
-        let render_pipeline = gpu.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &vs_module,
-                entry_point: "main", // 1.
-                buffers: &[], // 2.
-            },
-            fragment: Some(wgpu::FragmentState { // 3.
-                module: &fs_module,
-                entry_point: "main",
-                targets: &[wgpu::ColorTargetState { // 4.
-                    format: gpu.sc_desc.format,
-                    alpha_blend: wgpu::BlendState::REPLACE,
-                    color_blend: wgpu::BlendState::REPLACE,
-                    write_mask: wgpu::ColorWrite::ALL,
-                }],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList, // 1.
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw, // 2.
-                cull_mode: wgpu::CullMode::Back,
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less, // 1.
-                stencil: wgpu::StencilState::default(), // 2.
-                bias: wgpu::DepthBiasState::default(),
-                // Setting this to true requires Features::DEPTH_CLAMPING
-                clamp_depth: false,
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1, // 2.
-                mask: !0, // 3.
-                alpha_to_coverage_enabled: false, // 4.
-            },
-        });
-        
         return Self{
             render_pipeline,
-            camera_obj
+            camera_obj,
+            meshes: Vec::new(),
+            sort_key: SortKey::default(),
+            draws_triangle: true,
+            files: None,
         }
     }
 
-    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>){
-        render_pass.set_pipeline(&self.render_pipeline); // 2.
-        render_pass.set_bind_group(self.camera_obj.binding, &self.camera_obj.bind_group, &[]); //TODO, the gpu object should know what its bind group is.
-        render_pass.draw(0..3, 0..1); // 3.
+    ///Effect for drawing `mesh::ColorVertex` meshes with the built in mesh shader.
+    pub fn for_meshes(gpu: &State, camera_obj: GPUObject<crate::camera::Uniforms>) -> Self{
+        let module = Shader::wgsl("mesh.wgsl", include_str!("mesh.wgsl")).create_module(&gpu.device)
+            .expect("built in shader validates");
+
+        let render_pipeline = Self::create_pipeline(gpu, &camera_obj, (&module, "vs_main"), (&module, "fs_main"),
+            &[crate::mesh::ColorVertex::desc()], Some("mesh.wgsl"));
+
+        return Self{
+            render_pipeline,
+            camera_obj,
+            meshes: Vec::new(),
+            sort_key: SortKey::default(),
+            draws_triangle: false,
+            files: None,
+        }
+    }
+
+    ///Effect for meshes with a custom vertex layout, the shaders must read the camera uniforms at group 0.
+    pub fn with_shaders(gpu: &State, camera_obj: GPUObject<crate::camera::Uniforms>,
+        vertex: (&wgpu::ShaderModule, &str), fragment: (&wgpu::ShaderModule, &str),
+        buffers: &[wgpu::VertexBufferLayout]) -> Self
+    {
+        let render_pipeline = Self::create_pipeline(gpu, &camera_obj, vertex, fragment, buffers, None);
+
+        return Self{
+            render_pipeline,
+            camera_obj,
+            meshes: Vec::new(),
+            sort_key: SortKey::default(),
+            draws_triangle: buffers.is_empty(),
+            files: None,
+        }
+    }
+
+    ///Effect with shaders loaded through `gpu.shaders`, rebuilt whenever hot reloading sees them change.
+    ///Takes the file and entry point of each stage, the shaders must read the camera uniforms at group 0.
+    pub fn from_files<P: AsRef<Path>>(gpu: &mut State, camera_obj: GPUObject<crate::camera::Uniforms>,
+        vertex: (P, &str), fragment: (P, &str), buffers: &[wgpu::VertexBufferLayout<'static>]) -> Result<Self, Error>
+    {
+        let files = ShaderFiles {
+            vertex: (vertex.0.as_ref().canonicalize()?, vertex.1.to_string()),
+            fragment: (fragment.0.as_ref().canonicalize()?, fragment.1.to_string()),
+            buffers: buffers.to_vec(),
+        };
+        gpu.shaders.load(&gpu.device, &files.vertex.0)?;
+        gpu.shaders.load(&gpu.device, &files.fragment.0)?;
+        let render_pipeline = Self::pipeline_from_files(gpu, &camera_obj, &files).expect("shaders were just loaded");
+
+        return Ok(Self{
+            render_pipeline,
+            camera_obj,
+            meshes: Vec::new(),
+            sort_key: SortKey::default(),
+            draws_triangle: buffers.is_empty(),
+            files: Some(files),
+        })
+    }
+
+    fn pipeline_from_files(gpu: &State, camera_obj: &GPUObject<crate::camera::Uniforms>,
+        files: &ShaderFiles) -> Option<Arc<wgpu::RenderPipeline>>
+    {
+        let vertex = gpu.shaders.get(&files.vertex.0)?;
+        let fragment = gpu.shaders.get(&files.fragment.0)?;
+        let key = format!("{}:{} {}:{}", files.vertex.0.display(), files.vertex.1,
+            files.fragment.0.display(), files.fragment.1);
+        Some(Self::create_pipeline(gpu, camera_obj, (vertex, &files.vertex.1), (fragment, &files.fragment.1),
+            &files.buffers, Some(&key)))
+    }
+
+    pub fn with_sort_key(mut self, sort_key: SortKey) -> Self {
+        self.sort_key = sort_key;
+        self
+    }
+
+    pub fn add_mesh(&mut self, mesh: Mesh){
+        self.meshes.push(mesh);
+    }
+
+    ///`cache_key` names the shaders, every effect here has the same camera uniforms layout at group 0.
+    ///Effects built from modules the caller passed in go without one and get their own pipeline.
+    fn create_pipeline(gpu: &State, camera_obj: &GPUObject<crate::camera::Uniforms>,
+        vertex: (&wgpu::ShaderModule, &str), fragment: (&wgpu::ShaderModule, &str),
+        buffers: &[wgpu::VertexBufferLayout], cache_key: Option<&str>) -> Arc<wgpu::RenderPipeline>
+    {
+        let mut builder = PipelineBuilder::new(gpu, vertex, fragment)
+            .with_label("Render Pipeline")
+            .with_bind_group_layouts(&[&camera_obj.layout])
+            .with_vertex_buffers(buffers);
+        if let Some(key) = cache_key {
+            builder = builder.with_cache_key(key);
+        }
+
+        return gpu.pipelines.get_or_build(&gpu.device, &builder);
     }
 
     pub fn write_buffer(&self, gpu: &State, buffer: &wgpu::Buffer, bytes: impl bytemuck::Pod ){
@@ -202,4 +645,34 @@ impl BasicEffect {
         gpu.queue.write_buffer(&self.camera_obj.buffer, 0, bytemuck::cast_slice(&[bytes]));
     }
 
-}
\ No newline at end of file
+}
+
+impl Effect for BasicEffect {
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>){
+        render_pass.set_pipeline(&self.render_pipeline); // 2.
+        render_pass.set_bind_group(self.camera_obj.binding, &self.camera_obj.bind_group, &[]); //TODO, the gpu object should know what its bind group is.
+        if self.meshes.is_empty() && self.draws_triangle {
+            render_pass.draw(0..3, 0..1); // 3.
+        }
+        for mesh in &self.meshes {
+            mesh.draw(render_pass);
+        }
+    }
+
+    fn sort_key(&self) -> SortKey {
+        self.sort_key
+    }
+
+    fn reload(&mut self, gpu: &State, changed: &[PathBuf]) {
+        let files = match &self.files {
+            Some(files) => files,
+            None => return,
+        };
+        if !changed.contains(&files.vertex.0) && !changed.contains(&files.fragment.0) {
+            return;
+        }
+        if let Some(render_pipeline) = Self::pipeline_from_files(gpu, &self.camera_obj, files) {
+            self.render_pipeline = render_pipeline;
+        }
+    }
+}
@@ -0,0 +1,577 @@
+//!Render graph, the passes drawn each frame with the attachments they draw into.
+//!
+//!Every pass names its color and depth attachments, either the frame, the state's depth buffer, or a texture
+//!the graph owns, and lists the passes it depends on. Passes run in dependency order, then in the order added.
+use crate::gpu::{self, FrameSettings, DEFAULT_CLEAR_COLOR};
+use crate::texture;
+use crate::Error;
+use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+///Name of the pass `gpu::State` starts with, drawing into the frame.
+pub const MAIN_PASS: &str = "main";
+
+///Lets effects be found again by type without every effect writing its own downcasting.
+pub trait AsAny: Any {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+///Something drawn inside a pass, with its own pipeline and bind groups.
+pub trait Effect: AsAny {
+    fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>);
+
+    ///Called after the frame is resized and the graph's textures are recreated,
+    ///effects sampling those textures rebuild their bind groups here.
+    fn resize(&mut self, _device: &wgpu::Device, _textures: &Textures) {}
+
+    ///Called between frames when hot reloading recompiled the shader files in `changed`,
+    ///effects built from those files rebuild their pipelines from `gpu.shaders`.
+    fn reload(&mut self, _gpu: &gpu::State, _changed: &[PathBuf]) {}
+
+    ///Where the effect is drawn among the others in its pass, read every frame.
+    fn sort_key(&self) -> SortKey {
+        SortKey::default()
+    }
+}
+
+///Draw layers, every opaque effect in a pass is drawn before any transparent one, and overlays last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    ///Drawn front to back so the depth test throws away hidden fragments early.
+    Opaque,
+    ///Drawn back to front so blending sees what's behind.
+    Transparent,
+    ///Drawn in increasing `depth`, like UI on top of the scene.
+    Overlay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub layer: Layer,
+    ///Distance from the camera for opaque and transparent effects, the draw order for overlays.
+    ///Effects with equal keys draw in the order they were added.
+    pub depth: f32,
+}
+
+impl SortKey {
+    pub fn new(layer: Layer, depth: f32) -> Self {
+        Self { layer, depth }
+    }
+
+    fn draw_cmp(&self, other: &Self) -> Ordering {
+        let depth = self.depth.partial_cmp(&other.depth).unwrap_or(Ordering::Equal);
+        self.layer.cmp(&other.layer).then(match self.layer {
+            Layer::Transparent => depth.reverse(),
+            Layer::Opaque | Layer::Overlay => depth,
+        })
+    }
+}
+
+impl Default for SortKey {
+    fn default() -> Self {
+        Self::new(Layer::Opaque, 0.0)
+    }
+}
+
+///Names one registered effect, to get it back or remove it when several share a type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EffectId(usize);
+
+impl EffectId {
+    fn next() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        EffectId(NEXT.fetch_add(1, AtomicOrdering::Relaxed))
+    }
+}
+
+pub struct EffectSlot {
+    pub id: EffectId,
+    pub effect: Box<dyn Effect>,
+}
+
+///Indices of `keys` in the order they're drawn.
+fn draw_order(keys: &[SortKey]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..keys.len()).collect();
+    //Stable, so ties keep the order effects were added in.
+    order.sort_by(|&a, &b| keys[a].draw_cmp(&keys[b]));
+    order
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attachment {
+    ///The swap chain frame, or the offscreen color target when headless.
+    ///When multisampling, passes draw into a multisampled target that's resolved into it.
+    Frame,
+    ///The depth buffer `gpu::State` keeps the size of the frame, multisampled like the frame.
+    ///When multisampling it can't share a pass with graph textures, give such passes a depth texture instead.
+    Depth,
+    ///A texture added to the graph with `RenderGraph::add_texture`.
+    Texture(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorAttachment {
+    pub attachment: Attachment,
+    pub ops: wgpu::Operations<wgpu::Color>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthAttachment {
+    pub attachment: Attachment,
+    pub depth_ops: wgpu::Operations<f32>,
+    ///Only used when the attachment's format has a stencil aspect.
+    pub stencil_ops: Option<wgpu::Operations<u32>>,
+}
+
+pub struct Pass {
+    pub name: String,
+    pub color: Vec<ColorAttachment>,
+    pub depth: Option<DepthAttachment>,
+    ///Names of passes that have to run first.
+    pub dependencies: Vec<String>,
+    ///Drawn sorted by their `Effect::sort_key`.
+    pub effects: Vec<EffectSlot>,
+}
+
+impl Pass {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            color: Vec::new(),
+            depth: None,
+            dependencies: Vec::new(),
+            effects: Vec::new(),
+        }
+    }
+
+    pub fn with_color(mut self, attachment: Attachment, ops: wgpu::Operations<wgpu::Color>) -> Self {
+        self.color.push(ColorAttachment { attachment, ops });
+        self
+    }
+
+    pub fn with_depth(mut self, attachment: Attachment, depth_ops: wgpu::Operations<f32>) -> Self {
+        self.depth = Some(DepthAttachment { attachment, depth_ops, stencil_ops: None });
+        self
+    }
+
+    ///Stencil operations for the depth attachment set with `with_depth`.
+    pub fn with_stencil(mut self, stencil_ops: wgpu::Operations<u32>) -> Self {
+        if let Some(depth) = &mut self.depth {
+            depth.stencil_ops = Some(stencil_ops);
+        }
+        self
+    }
+
+    pub fn after(mut self, pass: &str) -> Self {
+        self.dependencies.push(pass.to_string());
+        self
+    }
+
+    pub fn add_effect<E: Effect>(&mut self, effect: E) -> EffectId {
+        let id = EffectId::next();
+        self.effects.push(EffectSlot { id, effect: Box::new(effect) });
+        id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureSize {
+    ///Follows the frame, recreated when it's resized.
+    Frame,
+    ///A fraction or multiple of the frame, like a half resolution bloom target.
+    Scaled(f32),
+    Fixed(u32, u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureDesc {
+    pub size: TextureSize,
+    ///A depth format makes a depth texture with a comparison sampler, like a shadow map.
+    pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsage,
+}
+
+impl TextureDesc {
+    ///A texture passes draw into and later passes sample from.
+    pub fn new(size: TextureSize, format: wgpu::TextureFormat) -> Self {
+        Self {
+            size,
+            format,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+        }
+    }
+}
+
+///The textures a graph owns, by name.
+#[derive(Default)]
+pub struct Textures {
+    descs: HashMap<String, TextureDesc>,
+    textures: HashMap<String, texture::Texture>,
+}
+
+impl Textures {
+    pub fn get(&self, name: &str) -> Option<&texture::Texture> {
+        self.textures.get(name)
+    }
+
+    fn create(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor, name: &str,
+        desc: &TextureDesc) -> texture::Texture
+    {
+        let (width, height) = match desc.size {
+            TextureSize::Frame => (sc_desc.width, sc_desc.height),
+            TextureSize::Scaled(scale) => (
+                ((sc_desc.width as f32 * scale) as u32).max(1),
+                ((sc_desc.height as f32 * scale) as u32).max(1),
+            ),
+            TextureSize::Fixed(width, height) => (width, height),
+        };
+        let target_desc = wgpu::SwapChainDescriptor {
+            usage: desc.usage,
+            format: desc.format,
+            width,
+            height,
+            present_mode: sc_desc.present_mode,
+        };
+        if texture::Texture::is_depth_format(desc.format) {
+            texture::Texture::create_depth_texture_with_format(device, &target_desc, desc.format, 1, name)
+        } else {
+            texture::Texture::create_render_target(device, &target_desc, name)
+        }
+    }
+}
+
+///What `Attachment::Frame` and `Attachment::Depth` are this frame, and how the main pass clears them.
+pub(crate) struct FrameTargets<'a> {
+    pub frame: &'a wgpu::TextureView,
+    ///Drawn into in place of the frame and resolved into it when multisampling.
+    pub multisampled: Option<&'a wgpu::TextureView>,
+    pub depth: &'a wgpu::TextureView,
+    pub depth_format: wgpu::TextureFormat,
+    pub settings: &'a FrameSettings,
+}
+
+#[derive(Default)]
+pub struct RenderGraph {
+    passes: Vec<Pass>,
+    textures: Textures,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///The graph `gpu::State` starts with, one pass clearing the frame and depth buffer.
+    ///Inside `gpu::State` its clears are replaced by `State::frame`.
+    pub fn with_main_pass() -> Self {
+        let mut graph = Self::new();
+        graph.add_pass(Pass::new(MAIN_PASS)
+            .with_color(Attachment::Frame, wgpu::Operations {
+                load: wgpu::LoadOp::Clear(DEFAULT_CLEAR_COLOR),
+                store: true,
+            })
+            .with_depth(Attachment::Depth, wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: true,
+            }));
+        graph
+    }
+
+    ///Adds a pass, replacing any pass with the same name.
+    pub fn add_pass(&mut self, pass: Pass) {
+        match self.passes.iter_mut().find(|p| p.name == pass.name) {
+            Some(existing) => *existing = pass,
+            None => self.passes.push(pass),
+        }
+    }
+
+    pub fn remove_pass(&mut self, name: &str) -> Option<Pass> {
+        let index = self.passes.iter().position(|p| p.name == name)?;
+        Some(self.passes.remove(index))
+    }
+
+    pub fn pass(&self, name: &str) -> Option<&Pass> {
+        self.passes.iter().find(|p| p.name == name)
+    }
+
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut Pass> {
+        self.passes.iter_mut().find(|p| p.name == name)
+    }
+
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    ///Adds an effect to the named pass.
+    pub fn add_effect<E: Effect>(&mut self, pass: &str, effect: E) -> Result<EffectId, Error> {
+        let pass = self.pass_mut(pass).ok_or_else(|| Error::RenderGraph(format!("no pass named `{}`", pass)))?;
+        Ok(pass.add_effect(effect))
+    }
+
+    fn slot(&self, id: EffectId) -> Option<&EffectSlot> {
+        self.passes.iter().flat_map(|p| p.effects.iter()).find(|slot| slot.id == id)
+    }
+
+    ///The effect registered as `id`, `None` if it was removed or isn't an `E`.
+    pub fn effect<E: Effect>(&self, id: EffectId) -> Option<&E> {
+        self.slot(id).and_then(|slot| (*slot.effect).as_any().downcast_ref::<E>())
+    }
+
+    pub fn effect_mut<E: Effect>(&mut self, id: EffectId) -> Option<&mut E> {
+        self.passes.iter_mut().flat_map(|p| p.effects.iter_mut()).find(|slot| slot.id == id)
+            .and_then(|slot| (*slot.effect).as_any_mut().downcast_mut::<E>())
+    }
+
+    pub fn remove_effect(&mut self, id: EffectId) -> Option<Box<dyn Effect>> {
+        for pass in &mut self.passes {
+            if let Some(index) = pass.effects.iter().position(|slot| slot.id == id) {
+                return Some(pass.effects.remove(index).effect);
+            }
+        }
+        None
+    }
+
+    ///The first effect of type `E` in any pass.
+    pub fn find_effect<E: Effect>(&self) -> Option<&E> {
+        self.passes.iter().flat_map(|p| p.effects.iter()).find_map(|slot| (*slot.effect).as_any().downcast_ref::<E>())
+    }
+
+    pub fn find_effect_mut<E: Effect>(&mut self) -> Option<&mut E> {
+        self.passes.iter_mut().flat_map(|p| p.effects.iter_mut())
+            .find_map(|slot| (*slot.effect).as_any_mut().downcast_mut::<E>())
+    }
+
+    ///Creates a texture passes can draw into as `Attachment::Texture(name)`, replacing one with the same name.
+    pub fn add_texture(&mut self, device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor, name: &str,
+        desc: TextureDesc)
+    {
+        let texture = Textures::create(device, sc_desc, name, &desc);
+        self.textures.descs.insert(name.to_string(), desc);
+        self.textures.textures.insert(name.to_string(), texture);
+    }
+
+    pub fn textures(&self) -> &Textures {
+        &self.textures
+    }
+
+    ///Recreates the textures that follow the frame size and lets every effect know.
+    pub(crate) fn resize(&mut self, device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor) {
+        for (name, desc) in &self.textures.descs {
+            if let TextureSize::Fixed(..) = desc.size {
+                continue;
+            }
+            let texture = Textures::create(device, sc_desc, name, desc);
+            self.textures.textures.insert(name.clone(), texture);
+        }
+        for pass in &mut self.passes {
+            for slot in &mut pass.effects {
+                slot.effect.resize(device, &self.textures);
+            }
+        }
+    }
+
+    pub(crate) fn reload(&mut self, gpu: &gpu::State, changed: &[PathBuf]) {
+        for pass in &mut self.passes {
+            for slot in &mut pass.effects {
+                slot.effect.reload(gpu, changed);
+            }
+        }
+    }
+
+    ///Indices of the passes in the order they run.
+    pub fn order(&self) -> Result<Vec<usize>, Error> {
+        for pass in &self.passes {
+            if let Some(missing) = pass.dependencies.iter().find(|d| self.pass(d).is_none()) {
+                return Err(Error::RenderGraph(format!("pass `{}` depends on missing pass `{}`", pass.name, missing)));
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.passes.len());
+        let mut placed = vec![false; self.passes.len()];
+        while order.len() < self.passes.len() {
+            let ready = (0..self.passes.len()).find(|&i| {
+                !placed[i] && self.passes[i].dependencies.iter().all(|d| {
+                    self.passes.iter().position(|p| &p.name == d).is_some_and(|j| placed[j])
+                })
+            });
+            match ready {
+                Some(i) => {
+                    placed[i] = true;
+                    order.push(i);
+                }
+                None => {
+                    let stuck: Vec<&str> = (0..self.passes.len()).filter(|&i| !placed[i])
+                        .map(|i| self.passes[i].name.as_str()).collect();
+                    return Err(Error::RenderGraph(format!("dependency cycle between {}", stuck.join(", "))));
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    ///Every attachment of a pass needs the same sample count, but only `Frame` and `Depth` are multisampled.
+    fn check_sample_counts(&self, multisampled: bool) -> Result<(), Error> {
+        if !multisampled {
+            return Ok(());
+        }
+        for pass in &self.passes {
+            let mut attachments = pass.color.iter().map(|c| &c.attachment).chain(pass.depth.iter().map(|d| &d.attachment));
+            let first = match attachments.next() {
+                Some(first) => matches!(first, Attachment::Texture(_)),
+                None => continue,
+            };
+            if attachments.any(|attachment| matches!(attachment, Attachment::Texture(_)) != first) {
+                return Err(Error::RenderGraph(format!(
+                    "pass `{}` mixes graph textures with the multisampled frame or depth buffer", pass.name)));
+            }
+        }
+        Ok(())
+    }
+
+    fn view<'a>(&'a self, attachment: &Attachment, targets: &FrameTargets<'a>)
+        -> Result<&'a wgpu::TextureView, Error>
+    {
+        match attachment {
+            Attachment::Frame => Ok(targets.frame),
+            Attachment::Depth => Ok(targets.depth),
+            Attachment::Texture(name) => self.textures.get(name).map(|t| &t.view)
+                .ok_or_else(|| Error::RenderGraph(format!("no texture named `{}`", name))),
+        }
+    }
+
+    fn format(&self, attachment: &Attachment, targets: &FrameTargets) -> Option<wgpu::TextureFormat> {
+        match attachment {
+            Attachment::Frame => None,
+            Attachment::Depth => Some(targets.depth_format),
+            Attachment::Texture(name) => self.textures.descs.get(name).map(|desc| desc.format),
+        }
+    }
+
+    ///Records every pass, with the main pass clearing the way `targets.settings` asks.
+    pub(crate) fn record(&self, encoder: &mut wgpu::CommandEncoder, targets: &FrameTargets) -> Result<(), Error> {
+        let settings = targets.settings;
+        self.check_sample_counts(targets.multisampled.is_some())?;
+        for i in self.order()? {
+            let pass = &self.passes[i];
+            let main = pass.name == MAIN_PASS;
+            let color_attachments = pass.color.iter().map(|color| {
+                let mut ops = color.ops;
+                if main && color.attachment == Attachment::Frame {
+                    ops.load = settings.color_load();
+                }
+                let (attachment, resolve_target) = match (&color.attachment, targets.multisampled) {
+                    (Attachment::Frame, Some(multisampled)) => (multisampled, Some(targets.frame)),
+                    (attachment, _) => (self.view(attachment, targets)?, None),
+                };
+                Ok(wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment,
+                    resolve_target,
+                    ops,
+                })
+            }).collect::<Result<Vec<_>, Error>>()?;
+            let depth_stencil_attachment = match &pass.depth {
+                Some(d) => {
+                    let mut depth_ops = d.depth_ops;
+                    let mut stencil_ops = d.stencil_ops;
+                    if main && d.attachment == Attachment::Depth {
+                        depth_ops.load = settings.depth_load();
+                        stencil_ops = Some(wgpu::Operations { load: settings.stencil_load(), store: true });
+                    }
+                    let has_stencil = self.format(&d.attachment, targets).is_some_and(texture::Texture::has_stencil);
+                    Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                        attachment: self.view(&d.attachment, targets)?,
+                        depth_ops: Some(depth_ops),
+                        stencil_ops: if has_stencil { stencil_ops } else { None },
+                    })
+                }
+                None => None,
+            };
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(pass.name.as_str()),
+                color_attachments: &color_attachments,
+                depth_stencil_attachment,
+            });
+            if main {
+                if let Some(skybox) = settings.skybox() {
+                    skybox.render(&mut render_pass);
+                }
+            }
+            let keys: Vec<SortKey> = pass.effects.iter().map(|slot| slot.effect.sort_key()).collect();
+            for i in draw_order(&keys) {
+                pass.effects[i].effect.render(&mut render_pass);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_run_after_their_dependencies() {
+        let mut graph = RenderGraph::new();
+        graph.add_pass(Pass::new("ui").after("post"));
+        graph.add_pass(Pass::new("post").after("main"));
+        graph.add_pass(Pass::new("main").after("shadow"));
+        graph.add_pass(Pass::new("shadow"));
+        let names: Vec<&str> = graph.order().unwrap().into_iter().map(|i| graph.passes()[i].name.as_str()).collect();
+        assert_eq!(names, vec!["shadow", "main", "post", "ui"]);
+
+        graph.add_pass(Pass::new("shadow").after("ui"));
+        assert!(graph.order().is_err());
+    }
+
+    #[test]
+    fn multisampled_attachments_stay_together() {
+        let ops = wgpu::Operations { load: wgpu::LoadOp::Clear(1.0), store: true };
+        let mut graph = RenderGraph::with_main_pass();
+        graph.add_pass(Pass::new("shadow").with_depth(Attachment::Texture("shadow_map".to_string()), ops));
+        graph.add_pass(Pass::new("post").with_color(Attachment::Texture("bloom".to_string()), wgpu::Operations {
+            load: wgpu::LoadOp::Load,
+            store: true,
+        }));
+        assert!(graph.check_sample_counts(true).is_ok());
+
+        graph.pass_mut("post").unwrap().depth = Some(DepthAttachment {
+            attachment: Attachment::Depth,
+            depth_ops: ops,
+            stencil_ops: None,
+        });
+        assert!(graph.check_sample_counts(false).is_ok());
+        match graph.check_sample_counts(true) {
+            Err(Error::RenderGraph(message)) => assert!(message.contains("`post`")),
+            other => panic!("expected a render graph error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn effects_draw_by_layer_and_depth() {
+        let keys = [
+            SortKey::new(Layer::Overlay, 1.0),
+            SortKey::new(Layer::Transparent, 2.0),
+            SortKey::new(Layer::Opaque, 5.0),
+            SortKey::new(Layer::Transparent, 8.0),
+            SortKey::new(Layer::Overlay, 0.0),
+            SortKey::new(Layer::Opaque, 1.0),
+            SortKey::new(Layer::Opaque, 1.0),
+        ];
+        assert_eq!(draw_order(&keys), vec![5, 6, 2, 3, 1, 4, 0]);
+    }
+}
@@ -1,13 +1,14 @@
 use winit::event::{Event, VirtualKeyCode};
 use winit::event_loop::{ControlFlow, EventLoop};
-use winit_input_helper::WinitInputHelper;
 use winit::{ event::*};
 use crate::gpu;
+use crate::input::InputState;
+use crate::Error;
 
 pub trait App {
     fn new(gpu: &gpu::State) -> Self;
 
-    fn run(window_config: WindowConfig) -> Result<(), ()>
+    fn run(window_config: WindowConfig) -> Result<(), Error>
     where
         Self: 'static + Sized,
     {
@@ -18,12 +19,24 @@ pub trait App {
 
     fn on_update(&mut self, app: &mut AppWindow, delta: std::time::Duration);
 
-    fn on_draw(&self, app: &mut AppWindow, control_flow: &mut winit::event_loop::ControlFlow);
+    ///`alpha` is how far into the next fixed update this frame sits, always 1.0 with a variable timestep.
+    fn on_draw(&self, app: &mut AppWindow, control_flow: &mut winit::event_loop::ControlFlow, alpha: f32);
 
+    ///Raw device input, only delivered while the window has focus.
     fn on_input(&mut self, inputs: &winit::event::DeviceEvent);
 
+    ///Every event for the app's window, after the loop has handled resizing.
+    ///For typed text, the cursor position over the window, or pausing when focus is lost.
+    fn on_window_event(&mut self, _event: &winit::event::WindowEvent) {}
+
     fn on_resize(&mut self, physical_size: winit::dpi::PhysicalSize<u32>);
 
+    ///Asked when the window is closed or the exit key is pressed, returning false keeps running.
+    fn on_exit_requested(&mut self, _app: &mut AppWindow) -> bool {
+        true
+    }
+
+    ///Called exactly once however the loop ends, including an app setting `ControlFlow::Exit` itself.
     fn on_exit(&self);
     
 }
@@ -48,30 +61,46 @@ pub trait Go<A: App>{
         window: &AppWindow,
     ) -> Self;
 
-    fn run(window_config: WindowConfig) -> Result<(), ()>
+    fn run(window_config: WindowConfig) -> Result<(), Error>
     where
     Self: 'static + Sized,
     A: 'static,
     {
         let event_loop = EventLoop::new();
-        let mut window = window_config.make_window(&event_loop);
+        let mut window = window_config.make_window(&event_loop)?;
         let mut app = App::new(&window.gpu);
         let _game_loop = Self::new(&mut app, &mut window); //If you take this out app requires type annotation, since I might 
         //need to add in some underlying functionality in the future that will go here no point in refactoring to fix that.
 
         app.on_load(&mut window);
 
-        let mut input = WinitInputHelper::new();
         let mut last_render_time = std::time::Instant::now();
+        let mut fixed_clock = match window_config.timestep {
+            Timestep::Fixed { tick_rate, max_steps } => Some(FixedClock::new(tick_rate, max_steps)),
+            Timestep::Variable => None,
+        };
+        let exit_key = window_config.exit_key;
+        let mut exited = false;
     
         event_loop.run(move |event, _, mut control_flow| {
-            input.update(&event);
-            if input.key_pressed(VirtualKeyCode::Escape) {*control_flow = ControlFlow::Exit;}
+            //Checked once a frame against the helper's per frame presses, so the app is only asked once per press
+            //even when no update runs to end the step.
+            if window.input.update(&event) {
+                if let Some(key) = exit_key {
+                    if window.input.helper().key_pressed(key) && app.on_exit_requested(&mut window) {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+            }
             
             match event {
                 //Event main events are cleared with request a redraw?
-                Event::MainEventsCleared => window.window.request_redraw(),
-                Event::DeviceEvent { event, ..} => {
+                Event::MainEventsCleared => {
+                    //Between frames, so no pipeline changes while one is being drawn.
+                    window.gpu.reload_shaders();
+                    window.window.request_redraw();
+                }
+                Event::DeviceEvent { event, ..} if window.input.focused() => {
                     app.on_input(&event);
                 }
                 //Handle window specific events and other things winit picks up I guess.
@@ -80,9 +109,9 @@ pub trait Go<A: App>{
                     window_id,
                 } if window_id == window.window.id() => {
                     match event {
-                        WindowEvent::CloseRequested => {
-                            app.on_exit();
-                            *control_flow = ControlFlow::Exit},
+                        WindowEvent::CloseRequested if app.on_exit_requested(&mut window) => {
+                            *control_flow = ControlFlow::Exit;
+                        }
                         WindowEvent::Resized(physical_size) => {
                             app.on_resize(*physical_size);
                             window.gpu.resize(*physical_size);
@@ -93,17 +122,42 @@ pub trait Go<A: App>{
                         }
                         _ => {}
                     }
+                    app.on_window_event(event);
                 }
                 //I am unsure about the ordering of this, when is this happening in the course of the program?
                 Event::RedrawRequested(_) => {
                     let now = std::time::Instant::now();
                     let delta_time = now - last_render_time;
                     last_render_time = now;
-                    app.on_update(&mut window, delta_time);
-                    app.on_draw(&mut window, &mut control_flow);
+                    let alpha = match &mut fixed_clock {
+                        Some(clock) => {
+                            let (steps, alpha) = clock.advance(delta_time);
+                            for _ in 0..steps {
+                                app.on_update(&mut window, clock.step);
+                                window.input.end_step();
+                            }
+                            alpha
+                        }
+                        None => {
+                            app.on_update(&mut window, delta_time);
+                            window.input.end_step();
+                            1.0
+                        }
+                    };
+                    app.on_draw(&mut window, &mut control_flow, alpha);
+                }
+                Event::LoopDestroyed if !exited => {
+                    exited = true;
+                    app.on_exit();
                 }
                 _ => {}
             }//End match statement.
+
+            //Whoever asked for the exit, the app hears about it once.
+            if *control_flow == ControlFlow::Exit && !exited {
+                exited = true;
+                app.on_exit();
+            }
         });//End Run Loop.
     }//end Run Function
 }
@@ -111,12 +165,63 @@ pub trait Go<A: App>{
 pub struct AppWindow{
     pub gpu: gpu::State,
     pub window: winit::window::Window,
+    ///Keyboard and mouse since the last update, ask it from `on_update`.
+    pub input: InputState,
+}
+
+///How often `App::on_update` is called.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timestep {
+    ///Once per redraw with however much time has passed since the last one.
+    Variable,
+    ///`tick_rate` times a second with a constant delta, running at most `max_steps` updates per redraw
+    ///so a long stall doesn't spiral into ever more catching up. `tick_rate` has to be positive.
+    Fixed { tick_rate: f64, max_steps: u32 },
+}
+
+///Accumulates frame time and hands it out in whole fixed steps.
+struct FixedClock {
+    step: std::time::Duration,
+    max_steps: u32,
+    accumulator: std::time::Duration,
+}
+
+impl FixedClock {
+    fn new(tick_rate: f64, max_steps: u32) -> Self {
+        assert!(tick_rate > 0.0 && tick_rate.is_finite(), "tick rate must be positive, got {}", tick_rate);
+        Self {
+            step: std::time::Duration::from_secs_f64(1.0 / tick_rate),
+            max_steps,
+            accumulator: std::time::Duration::from_secs(0),
+        }
+    }
+
+    ///Returns how many updates to run for `elapsed` and the leftover fraction of a step for interpolation.
+    fn advance(&mut self, elapsed: std::time::Duration) -> (u32, f32) {
+        self.accumulator += elapsed;
+        let mut steps = 0;
+        while self.accumulator >= self.step && steps < self.max_steps {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        //Whatever couldn't be caught up on is dropped, only the partial step is kept.
+        if self.accumulator >= self.step {
+            self.accumulator = std::time::Duration::from_secs_f64(
+                self.accumulator.as_secs_f64() % self.step.as_secs_f64());
+        }
+        let alpha = (self.accumulator.as_secs_f64() / self.step.as_secs_f64()) as f32;
+        (steps, alpha)
+    }
 }
 
 pub struct WindowConfig{
     pub width: f64,
     pub height: f64,
     pub title: String,
+    pub timestep: Timestep,
+    pub graphics: gpu::Settings,
+    ///Pressing it asks `App::on_exit_requested` whether to quit, `None` turns it off.
+    pub exit_key: Option<VirtualKeyCode>,
 }
 
 impl WindowConfig{
@@ -125,10 +230,25 @@ impl WindowConfig{
             width,
             height,
             title,
+            timestep: Timestep::Variable,
+            graphics: gpu::Settings::default(),
+            exit_key: Some(VirtualKeyCode::Escape),
         }
     }
 
-    pub fn make_window(&self, event_loop: &EventLoop<()>) -> AppWindow {
+    pub fn with_exit_key(mut self, exit_key: Option<VirtualKeyCode>) -> Self {
+        self.exit_key = exit_key;
+        self
+    }
+
+    ///Panics unless `tick_rate` is a positive number of updates per second.
+    pub fn with_fixed_timestep(mut self, tick_rate: f64, max_steps: u32) -> Self {
+        assert!(tick_rate > 0.0 && tick_rate.is_finite(), "tick rate must be positive, got {}", tick_rate);
+        self.timestep = Timestep::Fixed { tick_rate, max_steps };
+        self
+    }
+
+    pub fn make_window(&self, event_loop: &EventLoop<()>) -> Result<AppWindow, Error> {
         let window = {
             use winit::window::WindowBuilder;
             use winit::dpi::LogicalSize;
@@ -137,16 +257,37 @@ impl WindowConfig{
                 .with_title(self.title.as_str())
                 .with_inner_size(size)
                 .with_min_inner_size(size)
-                .build(&event_loop)
-                .unwrap()
+                .build(&event_loop)?
         };
 
         use futures::executor::block_on;
-        let gpu = block_on(gpu::State::new(&window));
+        let gpu = block_on(gpu::State::new(&window, &self.graphics))?;
         
-        return AppWindow{
+        return Ok(AppWindow{
             gpu,
             window,
-        }
+            input: InputState::new(),
+        })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn fixed_clock_caps_catch_up() {
+        let mut clock = FixedClock::new(100.0, 4);
+        assert_eq!(clock.advance(Duration::from_millis(25)).0, 2);
+        let (steps, alpha) = clock.advance(Duration::from_millis(500));
+        assert_eq!(steps, 4);
+        assert!((0.0..1.0).contains(&alpha));
+    }
+
+    #[test]
+    #[should_panic(expected = "tick rate must be positive")]
+    fn fixed_timestep_rejects_zero_rate() {
+        WindowConfig::new(800.0, 600.0, "test".to_string()).with_fixed_timestep(0.0, 4);
+    }
+}
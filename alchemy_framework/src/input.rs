@@ -0,0 +1,581 @@
+//!Rebindable input, a table from actions to the keys and buttons that trigger them.
+//!
+//!`Bindings` follows presses and releases as they're handed to it, `ActionMap` instead asks the
+//!`InputState` every `AppWindow` keeps, and adds analog axes.
+//!
+//!Tables can be written out and read back from a small config format, one action per line:
+//!```text
+//!#Comments start with a hash.
+//!forward = Z, Up
+//!look = Button(1), Mouse(Right)
+//!```
+use crate::Error;
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
+use std::path::Path;
+use winit::event::{DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+use winit_input_helper::WinitInputHelper;
+
+///Something an action can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    ///A mouse button as window events report it.
+    Mouse(MouseButton),
+    ///A raw button id as `DeviceEvent::Button` reports it, the numbering depends on the platform.
+    Button(u32),
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{:?}", key),
+            Binding::Mouse(MouseButton::Other(n)) => write!(f, "Mouse({})", n),
+            Binding::Mouse(button) => write!(f, "Mouse({:?})", button),
+            Binding::Button(n) => write!(f, "Button({})", n),
+        }
+    }
+}
+
+impl std::str::FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(inner) = s.strip_prefix("Mouse(").and_then(|s| s.strip_suffix(')')) {
+            return match inner.trim() {
+                "Left" => Ok(Binding::Mouse(MouseButton::Left)),
+                "Right" => Ok(Binding::Mouse(MouseButton::Right)),
+                "Middle" => Ok(Binding::Mouse(MouseButton::Middle)),
+                n => n.parse().map(|n| Binding::Mouse(MouseButton::Other(n)))
+                    .map_err(|_| format!("unknown mouse button `{}`", n)),
+            };
+        }
+        if let Some(inner) = s.strip_prefix("Button(").and_then(|s| s.strip_suffix(')')) {
+            return inner.trim().parse().map(Binding::Button).map_err(|_| format!("unknown button `{}`", inner));
+        }
+        //winit's own serde names, so every key it knows can be named the same way it's spelled in code.
+        let deserializer: serde::de::value::StrDeserializer<serde::de::value::Error> = s.into_deserializer();
+        VirtualKeyCode::deserialize(deserializer).map(Binding::Key).map_err(|_| format!("unknown key `{}`", s))
+    }
+}
+
+///A set of things the player can do, named so bindings can be read from a config file.
+pub trait Action: Copy + Eq + Hash + 'static {
+    const ALL: &'static [Self];
+    fn name(self) -> &'static str;
+}
+
+///Which bindings trigger each action, and which bindings are currently held.
+#[derive(Debug, Clone)]
+pub struct Bindings<A: Action> {
+    bindings: HashMap<A, Vec<Binding>>,
+    held: HashSet<Binding>,
+}
+
+impl<A: Action> Bindings<A> {
+    pub fn new() -> Self {
+        Self {
+            bindings: HashMap::new(),
+            held: HashSet::new(),
+        }
+    }
+
+    ///Builds a table from a list of actions and what they're bound to, handy for defaults.
+    pub fn from_pairs(pairs: &[(A, &[Binding])]) -> Self {
+        let mut bindings = Self::new();
+        for (action, list) in pairs {
+            bindings.set(*action, list.to_vec());
+        }
+        bindings
+    }
+
+    ///Reads a config, see the module docs for the format.
+    pub fn parse(config: &str) -> Result<Self, Error> {
+        let mut bindings = Self::new();
+        for (i, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| Error::Bindings { line: i + 1, message };
+            let mut parts = line.splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim();
+            let list = parts.next().ok_or_else(|| error("expected `action = bindings`".to_string()))?;
+            let action = A::ALL.iter().copied().find(|a| a.name() == name)
+                .ok_or_else(|| error(format!("unknown action `{}`", name)))?;
+            let list = list.split(',').filter(|b| !b.trim().is_empty())
+                .map(|b| b.parse().map_err(error))
+                .collect::<Result<Vec<Binding>, Error>>()?;
+            bindings.set(action, list);
+        }
+        Ok(bindings)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    ///Writes every action out in the format `parse` reads.
+    pub fn to_config(&self) -> String {
+        let mut config = String::new();
+        for action in A::ALL {
+            let list: Vec<String> = self.get(*action).iter().map(|b| b.to_string()).collect();
+            config.push_str(&format!("{} = {}\n", action.name(), list.join(", ")));
+        }
+        config
+    }
+
+    ///Takes over every action `other` mentions, so a config only has to list what it changes.
+    pub fn merge(&mut self, other: Bindings<A>) {
+        for (action, list) in other.bindings {
+            self.set(action, list);
+        }
+    }
+
+    pub fn get(&self, action: A) -> &[Binding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn set(&mut self, action: A, bindings: Vec<Binding>) {
+        self.bindings.insert(action, bindings);
+    }
+
+    pub fn bind(&mut self, action: A, binding: Binding) {
+        let list = self.bindings.entry(action).or_default();
+        if !list.contains(&binding) {
+            list.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: A, binding: Binding) {
+        if let Some(list) = self.bindings.get_mut(&action) {
+            list.retain(|b| *b != binding);
+        }
+    }
+
+    pub fn clear(&mut self, action: A) {
+        self.bindings.remove(&action);
+    }
+
+    ///Records a press or release, returns true when some action uses the binding.
+    pub fn handle(&mut self, binding: Binding, state: ElementState) -> bool {
+        match state {
+            ElementState::Pressed => self.held.insert(binding),
+            ElementState::Released => self.held.remove(&binding),
+        };
+        self.bindings.values().any(|list| list.contains(&binding))
+    }
+
+    ///True while any of the action's bindings is held down.
+    pub fn is_held(&self, action: A) -> bool {
+        self.get(action).iter().any(|b| self.held.contains(b))
+    }
+
+    ///Forgets everything held, for when the window loses the inputs that would release them.
+    pub fn release_all(&mut self) {
+        self.held.clear();
+    }
+}
+
+impl<A: Action> Default for Bindings<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///Everything that happened to the keyboard and mouse since the last update, fed every event by the loop.
+///
+///Keys, mouse buttons, cursor motion and scroll are read from the loop's `WinitInputHelper` once each
+///frame's events are in. The helper only reads window events, so raw mouse motion and device buttons are
+///followed here instead. The helper also starts over every frame, while the presses, releases and motion
+///gathered here pile up until `end_step` is called after each `App::on_update`. With a fixed timestep a
+///press on a frame that runs no steps reaches the next step that does run, and a frame running several
+///steps only reports it to the first.
+pub struct InputState {
+    helper: WinitInputHelper,
+    ///Keys and mouse buttons with events this frame, the ones to ask the helper about when it ends.
+    touched: HashSet<Binding>,
+    ///Held when the last frame ended, so the presses the helper reports for key repeats aren't new ones.
+    was_held: HashSet<Binding>,
+    buttons: HashSet<u32>,
+    pressed: HashSet<Binding>,
+    released: HashSet<Binding>,
+    mouse_motion: (f64, f64),
+    cursor_motion: (f32, f32),
+    scroll: f32,
+    focused: bool,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self {
+            helper: WinitInputHelper::new(),
+            touched: HashSet::new(),
+            was_held: HashSet::new(),
+            buttons: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+            mouse_motion: (0.0, 0.0),
+            cursor_motion: (0.0, 0.0),
+            scroll: 0.0,
+            focused: true,
+        }
+    }
+
+    ///Returns true once the frame's events are all in.
+    pub fn update<T>(&mut self, event: &Event<T>) -> bool {
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::Focused(focused) => {
+                    self.focused = *focused;
+                    //Releases that happen while unfocused never arrive, the helper forgets its keys too.
+                    if !focused {
+                        self.was_held.clear();
+                        self.buttons.clear();
+                    }
+                }
+                WindowEvent::KeyboardInput { input: KeyboardInput { virtual_keycode: Some(key), .. }, .. } => {
+                    self.touched.insert(Binding::Key(*key));
+                }
+                WindowEvent::MouseInput { button, .. } => {
+                    self.touched.insert(Binding::Mouse(*button));
+                }
+                _ => {}
+            },
+            //Device events keep coming while another window has focus.
+            Event::DeviceEvent { event, .. } if self.focused => match event {
+                DeviceEvent::MouseMotion { delta } => {
+                    self.mouse_motion.0 += delta.0;
+                    self.mouse_motion.1 += delta.1;
+                }
+                DeviceEvent::Button { button, state } => self.handle_button(*button, *state),
+                _ => {}
+            },
+            _ => {}
+        }
+        let frame_done = self.helper.update(event);
+        if frame_done {
+            self.end_frame();
+        }
+        frame_done
+    }
+
+    fn handle_button(&mut self, button: u32, state: ElementState) {
+        match state {
+            ElementState::Pressed => if self.buttons.insert(button) {
+                self.pressed.insert(Binding::Button(button));
+            },
+            ElementState::Released => if self.buttons.remove(&button) {
+                self.released.insert(Binding::Button(button));
+            },
+        }
+    }
+
+    ///Keeps what the helper saw this frame before it starts over on the next one.
+    fn end_frame(&mut self) {
+        let touched: Vec<Binding> = self.touched.drain().collect();
+        for binding in touched {
+            let (pressed, released) = match binding {
+                Binding::Key(key) => (self.helper.key_pressed(key), self.helper.key_released(key)),
+                Binding::Mouse(button) => {
+                    let index = mouse_index(button);
+                    (self.helper.mouse_pressed(index), self.helper.mouse_released(index))
+                }
+                Binding::Button(_) => continue,
+            };
+            if pressed && !self.was_held.contains(&binding) {
+                self.pressed.insert(binding);
+            }
+            if released {
+                self.released.insert(binding);
+            }
+            if self.held(binding) {
+                self.was_held.insert(binding);
+            } else {
+                self.was_held.remove(&binding);
+            }
+        }
+        let (dx, dy) = self.helper.mouse_diff();
+        self.cursor_motion.0 += dx;
+        self.cursor_motion.1 += dy;
+        self.scroll += self.helper.scroll_diff();
+    }
+
+    ///Forgets the presses, releases and motion reported so far, the loop calls it after every update.
+    pub fn end_step(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.mouse_motion = (0.0, 0.0);
+        self.cursor_motion = (0.0, 0.0);
+        self.scroll = 0.0;
+    }
+
+    ///Whether the window has keyboard focus, device events are dropped while it doesn't.
+    pub fn focused(&self) -> bool {
+        self.focused
+    }
+
+    ///For anything that isn't wrapped here, like typed text or modifiers. Its queries cover the last frame
+    ///rather than the last update.
+    pub fn helper(&self) -> &WinitInputHelper {
+        &self.helper
+    }
+
+    ///Went down since the last update.
+    pub fn pressed(&self, binding: Binding) -> bool {
+        self.pressed.contains(&binding)
+    }
+
+    pub fn held(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.helper.key_held(key),
+            Binding::Mouse(button) => self.helper.mouse_held(mouse_index(button)),
+            Binding::Button(button) => self.buttons.contains(&button),
+        }
+    }
+
+    pub fn released(&self, binding: Binding) -> bool {
+        self.released.contains(&binding)
+    }
+
+    ///Raw mouse movement since the last update, keeps coming when the cursor is grabbed or at the screen edge.
+    pub fn mouse_motion(&self) -> (f64, f64) {
+        self.mouse_motion
+    }
+
+    ///How far the cursor moved over the window since the last update, in pixels.
+    pub fn cursor_motion(&self) -> (f32, f32) {
+        self.cursor_motion
+    }
+
+    ///Lines scrolled since the last update, the helper counts touchpad pixels 38 to a line.
+    pub fn scroll(&self) -> f32 {
+        self.scroll
+    }
+}
+
+///How the helper numbers mouse buttons.
+fn mouse_index(button: MouseButton) -> usize {
+    match button {
+        MouseButton::Left => 0,
+        MouseButton::Right => 1,
+        MouseButton::Middle => 2,
+        MouseButton::Other(n) => n as usize,
+    }
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///Where an axis gets its value from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AxisSource {
+    ///-1 while `negative` is held, 1 while `positive` is, 0 for both or neither.
+    Keys { negative: Binding, positive: Binding },
+    MouseX,
+    MouseY,
+    CursorX,
+    CursorY,
+    Scroll,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisBinding {
+    pub source: AxisSource,
+    pub scale: f32,
+}
+
+///Actions asked about by name each frame, as buttons through their `Bindings` or as axes.
+#[derive(Debug, Clone)]
+pub struct ActionMap<A: Action> {
+    pub bindings: Bindings<A>,
+    axes: HashMap<A, Vec<AxisBinding>>,
+}
+
+impl<A: Action> ActionMap<A> {
+    pub fn new(bindings: Bindings<A>) -> Self {
+        Self {
+            bindings,
+            axes: HashMap::new(),
+        }
+    }
+
+    pub fn bind_axis(&mut self, action: A, source: AxisSource, scale: f32) {
+        self.axes.entry(action).or_default().push(AxisBinding { source, scale });
+    }
+
+    pub fn clear_axis(&mut self, action: A) {
+        self.axes.remove(&action);
+    }
+
+    pub fn pressed(&self, input: &InputState, action: A) -> bool {
+        self.bindings.get(action).iter().any(|b| input.pressed(*b))
+    }
+
+    pub fn held(&self, input: &InputState, action: A) -> bool {
+        self.bindings.get(action).iter().any(|b| input.held(*b))
+    }
+
+    pub fn released(&self, input: &InputState, action: A) -> bool {
+        self.bindings.get(action).iter().any(|b| input.released(*b))
+    }
+
+    ///Sum of every source bound to the axis, each times its scale.
+    pub fn axis(&self, input: &InputState, action: A) -> f32 {
+        let axes = match self.axes.get(&action) {
+            Some(axes) => axes,
+            None => return 0.0,
+        };
+        axes.iter().map(|axis| {
+            let value = match axis.source {
+                AxisSource::Keys { negative, positive } => {
+                    let held = |b| if input.held(b) { 1.0 } else { 0.0 };
+                    held(positive) - held(negative)
+                }
+                AxisSource::MouseX => input.mouse_motion().0 as f32,
+                AxisSource::MouseY => input.mouse_motion().1 as f32,
+                AxisSource::CursorX => input.cursor_motion().0,
+                AxisSource::CursorY => input.cursor_motion().1,
+                AxisSource::Scroll => input.scroll(),
+            };
+            value * axis.scale
+        }).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Test {
+        Jump,
+        Fire,
+    }
+
+    impl Action for Test {
+        const ALL: &'static [Self] = &[Test::Jump, Test::Fire];
+
+        fn name(self) -> &'static str {
+            match self {
+                Test::Jump => "jump",
+                Test::Fire => "fire",
+            }
+        }
+    }
+
+    fn window(event: WindowEvent<'static>) -> Event<'static, ()> {
+        Event::WindowEvent { window_id: unsafe { winit::window::WindowId::dummy() }, event }
+    }
+
+    #[allow(deprecated)]
+    fn key(key: VirtualKeyCode, state: ElementState) -> Event<'static, ()> {
+        window(WindowEvent::KeyboardInput {
+            device_id: unsafe { winit::event::DeviceId::dummy() },
+            input: KeyboardInput { scancode: 0, state, virtual_keycode: Some(key), modifiers: Default::default() },
+            is_synthetic: false,
+        })
+    }
+
+    fn device(event: DeviceEvent) -> Event<'static, ()> {
+        Event::DeviceEvent { device_id: unsafe { winit::event::DeviceId::dummy() }, event }
+    }
+
+    ///Hands over a frame's events the way the loop does.
+    fn frame(input: &mut InputState, events: Vec<Event<'static, ()>>) {
+        input.update(&Event::<()>::NewEvents(winit::event::StartCause::Poll));
+        for event in &events {
+            assert!(!input.update(event));
+        }
+        assert!(input.update(&Event::<()>::MainEventsCleared));
+    }
+
+    #[test]
+    fn edges_last_until_the_step_ends() {
+        let space = Binding::Key(VirtualKeyCode::Space);
+        let mut input = InputState::new();
+        frame(&mut input, vec![key(VirtualKeyCode::Space, ElementState::Pressed)]);
+        assert!(input.pressed(space) && input.held(space));
+        //A frame that ran no steps keeps the press for the next one.
+        frame(&mut input, vec![]);
+        assert!(input.pressed(space));
+        input.end_step();
+        //Later steps of the same frame, and repeats, don't see it again.
+        assert!(!input.pressed(space) && input.held(space));
+        frame(&mut input, vec![key(VirtualKeyCode::Space, ElementState::Pressed)]);
+        assert!(!input.pressed(space) && input.held(space));
+
+        frame(&mut input, vec![key(VirtualKeyCode::Space, ElementState::Released)]);
+        assert!(input.released(space) && !input.held(space));
+        input.end_step();
+        assert!(!input.released(space));
+    }
+
+    #[test]
+    fn action_map_reads_buttons_and_axes() {
+        let mut actions = ActionMap::new(Bindings::from_pairs(&[
+            (Test::Jump, &[Binding::Key(VirtualKeyCode::Space)]),
+            (Test::Fire, &[Binding::Mouse(MouseButton::Left), Binding::Button(1)]),
+        ]));
+        actions.bind_axis(Test::Jump, AxisSource::Keys {
+            negative: Binding::Key(VirtualKeyCode::S),
+            positive: Binding::Key(VirtualKeyCode::W),
+        }, 1.0);
+        actions.bind_axis(Test::Jump, AxisSource::MouseY, 0.5);
+        actions.bind_axis(Test::Fire, AxisSource::Scroll, 2.0);
+
+        let mut input = InputState::new();
+        input.update(&device(DeviceEvent::Button { button: 1, state: ElementState::Pressed }));
+        assert!(actions.pressed(&input, Test::Fire) && actions.held(&input, Test::Fire));
+        assert!(!actions.held(&input, Test::Jump));
+
+        #[allow(deprecated)]
+        let scroll = WindowEvent::MouseWheel {
+            device_id: unsafe { winit::event::DeviceId::dummy() },
+            delta: winit::event::MouseScrollDelta::LineDelta(0.0, 1.5),
+            phase: winit::event::TouchPhase::Moved,
+            modifiers: Default::default(),
+        };
+        frame(&mut input, vec![key(VirtualKeyCode::W, ElementState::Pressed),
+            device(DeviceEvent::MouseMotion { delta: (3.0, 4.0) }), window(scroll)]);
+        assert_eq!(actions.axis(&input, Test::Jump), 3.0);
+        assert_eq!(actions.axis(&input, Test::Fire), 3.0);
+        input.end_step();
+        //Held keys keep their value, motion only counts for the update it happened in.
+        assert_eq!(actions.axis(&input, Test::Jump), 1.0);
+        assert_eq!(actions.axis(&input, Test::Fire), 0.0);
+
+        input.update(&device(DeviceEvent::Button { button: 1, state: ElementState::Released }));
+        assert!(actions.released(&input, Test::Fire) && !actions.held(&input, Test::Fire));
+
+        //Device input is ignored while another window has focus, and nothing stays held.
+        frame(&mut input, vec![window(WindowEvent::Focused(false)),
+            device(DeviceEvent::Button { button: 1, state: ElementState::Pressed })]);
+        assert!(!actions.held(&input, Test::Fire) && actions.axis(&input, Test::Jump) == 0.0);
+    }
+
+    #[test]
+    fn config_round_trips_and_tracks_held() {
+        let mut bindings = Bindings::<Test>::parse("# comment\njump = Space, W\nfire = Mouse(Left), Button(3)\n").unwrap();
+        assert_eq!(bindings.get(Test::Jump), &[Binding::Key(VirtualKeyCode::Space), Binding::Key(VirtualKeyCode::W)]);
+        assert_eq!(Bindings::<Test>::parse(&bindings.to_config()).unwrap().get(Test::Fire), bindings.get(Test::Fire));
+
+        assert!(bindings.handle(Binding::Key(VirtualKeyCode::Space), ElementState::Pressed));
+        assert!(bindings.handle(Binding::Key(VirtualKeyCode::W), ElementState::Pressed));
+        bindings.handle(Binding::Key(VirtualKeyCode::Space), ElementState::Released);
+        assert!(bindings.is_held(Test::Jump));
+        assert!(!bindings.is_held(Test::Fire));
+
+        match Bindings::<Test>::parse("jump = Space\nfire = Banana\n") {
+            Err(Error::Bindings { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("expected a binding error"),
+        }
+    }
+}
@@ -1,7 +1,24 @@
+//Lets the derives in alchemy_derive name this crate the same way inside it as outside.
+extern crate self as alchemy_framework;
+
 pub mod graphics;
 pub mod texture;
 pub mod camera;
 pub mod gpu;
+pub mod graph;
+pub mod pipeline;
+pub mod mesh;
+pub mod model;
+pub mod gltf_scene;
+pub mod golden;
+pub mod input;
+pub mod scene;
+pub mod shader;
+pub mod skybox;
+mod error;
+
+pub use error::Error;
+pub use wgpu;
 
 
 #[cfg(test)]
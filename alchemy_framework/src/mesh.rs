@@ -0,0 +1,82 @@
+use std::ops::Range;
+use wgpu::util::DeviceExt;
+
+pub use alchemy_derive::Vertex;
+
+///A `#[repr(C)]` type that can be uploaded into a vertex buffer, describes its own layout to the pipeline.
+///Usually derived with `#[derive(Vertex)]`.
+pub trait Vertex: bytemuck::Pod {
+    fn desc() -> wgpu::VertexBufferLayout<'static>;
+}
+
+///Position and color, the vertex the built in mesh shader expects.
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Debug, Vertex)]
+pub struct ColorVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+///Index data for a mesh, the width picks the index format.
+pub enum Indices<'a> {
+    U16(&'a [u16]),
+    U32(&'a [u32]),
+}
+
+pub struct Mesh {
+    pub name: String,
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: Option<(wgpu::Buffer, wgpu::IndexFormat)>,
+    ///Index count when indexed, otherwise vertex count.
+    pub num_elements: u32,
+    pub layout: wgpu::VertexBufferLayout<'static>,
+}
+
+impl Mesh {
+    pub fn new<V: Vertex>(device: &wgpu::Device, name: &str, vertices: &[V], indices: Option<Indices>) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(format!("VertexBuffer-{}", name).as_str()),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+
+        let (index_buffer, num_elements) = match indices {
+            Some(indices) => {
+                let (contents, format, count): (&[u8], _, _) = match indices {
+                    Indices::U16(i) => (bytemuck::cast_slice(i), wgpu::IndexFormat::Uint16, i.len()),
+                    Indices::U32(i) => (bytemuck::cast_slice(i), wgpu::IndexFormat::Uint32, i.len()),
+                };
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(format!("IndexBuffer-{}", name).as_str()),
+                    contents,
+                    usage: wgpu::BufferUsage::INDEX,
+                });
+                (Some((buffer, format)), count as u32)
+            }
+            None => (None, vertices.len() as u32),
+        };
+
+        Self {
+            name: name.to_string(),
+            vertex_buffer,
+            index_buffer,
+            num_elements,
+            layout: V::desc(),
+        }
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        self.draw_instanced(render_pass, 0..1);
+    }
+
+    pub fn draw_instanced<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, instances: Range<u32>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        match &self.index_buffer {
+            Some((buffer, format)) => {
+                render_pass.set_index_buffer(buffer.slice(..), *format);
+                render_pass.draw_indexed(0..self.num_elements, 0, instances);
+            }
+            None => render_pass.draw(0..self.num_elements, instances),
+        }
+    }
+}
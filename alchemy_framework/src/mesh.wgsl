@@ -0,0 +1,26 @@
+[[block]]
+struct Uniforms {
+    view_position: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> u_camera: Uniforms;
+
+[[location(0)]] var<in> in_position: vec3<f32>;
+[[location(1)]] var<in> in_color_vs: vec3<f32>;
+[[location(0)]] var<out> out_color_vs: vec3<f32>;
+[[builtin(position)]] var<out> out_position: vec4<f32>;
+
+[[stage(vertex)]]
+fn vs_main() {
+    out_color_vs = in_color_vs;
+    out_position = u_camera.view_proj * vec4<f32>(in_position, 1.0);
+}
+
+[[location(0)]] var<in> in_color_fs: vec3<f32>;
+[[location(0)]] var<out> out_color_fs: vec4<f32>;
+
+[[stage(fragment)]]
+fn fs_main() {
+    out_color_fs = vec4<f32>(in_color_fs, 1.0);
+}
@@ -0,0 +1,234 @@
+//!Wavefront OBJ + MTL loading into meshes and materials ready to draw.
+use crate::mesh::{Indices, Mesh, Vertex};
+use crate::texture;
+use crate::Error;
+use cgmath::{InnerSpace, Vector2, Vector3, Zero};
+use std::path::Path;
+#[allow(unused_imports)]
+use log::{error, warn, info, debug, trace};
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Debug, Vertex)]
+pub struct ModelVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+    pub tangent: [f32; 3],
+    pub bitangent: [f32; 3],
+}
+
+///Diffuse and normal map bound with `Texture::build_texture_bind_group_layout`.
+pub struct Material {
+    pub name: String,
+    pub diffuse_texture: texture::Texture,
+    pub normal_texture: texture::Texture,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Material {
+    pub fn new(device: &wgpu::Device, name: &str, diffuse_texture: texture::Texture,
+        normal_texture: texture::Texture, layout: &wgpu::BindGroupLayout) -> Self
+    {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&normal_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
+                },
+            ],
+            label: Some(name),
+        });
+
+        Self {
+            name: name.to_string(),
+            diffuse_texture,
+            normal_texture,
+            bind_group,
+        }
+    }
+}
+
+pub struct ModelMesh {
+    pub mesh: Mesh,
+    ///Index into `Model::materials`.
+    pub material: usize,
+}
+
+pub struct Model {
+    pub meshes: Vec<ModelMesh>,
+    pub materials: Vec<Material>,
+}
+
+impl Model {
+    ///Loads an OBJ file and its MTL libraries, texture paths are resolved next to the OBJ.
+    ///Materials without a diffuse or normal map get a plain white or flat normal texture.
+    pub fn load<P: AsRef<Path>>(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout,
+        path: P) -> Result<Self, Error>
+    {
+        let path = path.as_ref();
+        let (obj_models, obj_materials) = tobj::load_obj(path, &tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ..Default::default()
+        })?;
+        let obj_materials = obj_materials.unwrap_or_else(|e| {
+            warn!("Failed to load materials for {}: {}", path.display(), e);
+            Vec::new()
+        });
+
+        let containing_folder = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut materials = Vec::new();
+        for mat in obj_materials {
+            let diffuse_texture = match mat.diffuse_texture.as_str() {
+                "" => solid_texture(device, queue, [255, 255, 255, 255], "default_diffuse", false)?,
+                file => texture::Texture::load_with_mipmaps(device, queue, containing_folder.join(file), false)?,
+            };
+            let normal_texture = match mat.normal_texture.as_str() {
+                "" => solid_texture(device, queue, [128, 128, 255, 255], "default_normal", true)?,
+                file => texture::Texture::load_with_mipmaps(device, queue, containing_folder.join(file), true)?,
+            };
+            materials.push(Material::new(device, &mat.name, diffuse_texture, normal_texture, layout));
+        }
+        //Meshes always point at a material, so a file without any still gets the defaults.
+        if materials.is_empty() {
+            let diffuse_texture = solid_texture(device, queue, [255, 255, 255, 255], "default_diffuse", false)?;
+            let normal_texture = solid_texture(device, queue, [128, 128, 255, 255], "default_normal", true)?;
+            materials.push(Material::new(device, "default", diffuse_texture, normal_texture, layout));
+        }
+
+        let meshes = obj_models.iter().map(|m| {
+            let (vertices, indices) = vertices_from_obj(&m.mesh);
+            let mesh = Mesh::new(device, &m.name, &vertices, Some(Indices::U32(&indices)));
+            let material = m.mesh.material_id.filter(|id| *id < materials.len()).unwrap_or(0);
+            ModelMesh { mesh, material }
+        }).collect();
+
+        Ok(Self { meshes, materials })
+    }
+
+    ///Draws every mesh with its material bound at `material_group`.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, material_group: u32) {
+        for mesh in &self.meshes {
+            render_pass.set_bind_group(material_group, &self.materials[mesh.material].bind_group, &[]);
+            mesh.mesh.draw(render_pass);
+        }
+    }
+}
+
+pub(crate) fn solid_texture(device: &wgpu::Device, queue: &wgpu::Queue, color: [u8; 4], label: &str,
+    is_normal_map: bool) -> Result<texture::Texture, Error>
+{
+    let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(color)));
+    Ok(texture::Texture::from_image(device, queue, &img, Some(label), is_normal_map)?)
+}
+
+///Builds vertices from a single index tobj mesh, flipping v so textures aren't upside down.
+pub fn vertices_from_obj(mesh: &tobj::Mesh) -> (Vec<ModelVertex>, Vec<u32>) {
+    let mut vertices: Vec<ModelVertex> = (0..mesh.positions.len() / 3).map(|i| {
+        let tex_coords = if mesh.texcoords.len() >= i * 2 + 2 {
+            [mesh.texcoords[i * 2], 1.0 - mesh.texcoords[i * 2 + 1]]
+        } else {
+            [0.0, 0.0]
+        };
+        let normal = if mesh.normals.len() >= i * 3 + 3 {
+            [mesh.normals[i * 3], mesh.normals[i * 3 + 1], mesh.normals[i * 3 + 2]]
+        } else {
+            [0.0, 0.0, 0.0]
+        };
+        ModelVertex {
+            position: [mesh.positions[i * 3], mesh.positions[i * 3 + 1], mesh.positions[i * 3 + 2]],
+            tex_coords,
+            normal,
+            tangent: [0.0; 3],
+            bitangent: [0.0; 3],
+        }
+    }).collect();
+
+    compute_tangents(&mut vertices, &mesh.indices);
+    (vertices, mesh.indices.clone())
+}
+
+///Fills in tangents and bitangents from positions and uvs, averaged over every triangle sharing a vertex.
+///Triangles with degenerate uvs are skipped.
+pub fn compute_tangents(vertices: &mut [ModelVertex], indices: &[u32]) {
+    let mut tangents = vec![Vector3::zero(); vertices.len()];
+    let mut bitangents = vec![Vector3::zero(); vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let v0 = vertices[triangle[0] as usize];
+        let v1 = vertices[triangle[1] as usize];
+        let v2 = vertices[triangle[2] as usize];
+
+        let pos0: Vector3<f32> = v0.position.into();
+        let pos1: Vector3<f32> = v1.position.into();
+        let pos2: Vector3<f32> = v2.position.into();
+        let uv0: Vector2<f32> = v0.tex_coords.into();
+        let uv1: Vector2<f32> = v1.tex_coords.into();
+        let uv2: Vector2<f32> = v2.tex_coords.into();
+
+        let delta_pos1 = pos1 - pos0;
+        let delta_pos2 = pos2 - pos0;
+        let delta_uv1 = uv1 - uv0;
+        let delta_uv2 = uv2 - uv0;
+
+        let det = delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x;
+        if det.abs() < f32::EPSILON {
+            continue;
+        }
+        let r = 1.0 / det;
+        let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
+        let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * r;
+
+        for i in triangle {
+            tangents[*i as usize] += tangent;
+            bitangents[*i as usize] += bitangent;
+        }
+    }
+
+    for (i, vertex) in vertices.iter_mut().enumerate() {
+        if tangents[i].magnitude2() > 0.0 {
+            vertex.tangent = tangents[i].normalize().into();
+        }
+        if bitangents[i].magnitude2() > 0.0 {
+            vertex.bitangent = bitangents[i].normalize().into();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tangents_follow_uv_axes() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1\n";
+        let (models, _) = tobj::load_obj_buf(&mut obj.as_bytes(), &tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ..Default::default()
+        }, |_| Err(tobj::LoadError::OpenFileFailed)).unwrap();
+
+        let (vertices, indices) = vertices_from_obj(&models[0].mesh);
+        assert_eq!(indices.len(), 3);
+        //v is flipped on load, so the bitangent points down the y axis.
+        for v in &vertices {
+            assert_eq!(v.tangent, [1.0, 0.0, 0.0]);
+            assert_eq!(v.bitangent, [0.0, -1.0, 0.0]);
+            assert_eq!(v.normal, [0.0, 0.0, 1.0]);
+        }
+    }
+}
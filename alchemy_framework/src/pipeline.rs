@@ -0,0 +1,386 @@
+//!Render pipelines built from defaults that suit the framework's targets, overriding only what an effect needs.
+//!
+//!`PipelineCache` shares pipelines between builders that agree on their cache key, entry points, number of
+//!bind group layouts, vertex buffers and settings. wgpu gives shader modules and bind group layouts no identity
+//!to compare, so the cache key has to name them: a key must only ever be used with one set of modules and
+//!layouts, or a builder gets a pipeline made from another builder's shaders.
+use crate::gpu;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+///How a pipeline's output is combined with what's already in the target.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Blend {
+    ///Overwrites the target, for opaque geometry.
+    Replace,
+    ///Classic transparency with straight alpha.
+    Alpha,
+    ///Adds onto the target scaled by alpha, for glows and particles.
+    Additive,
+    ///Transparency for colors already multiplied by their alpha.
+    Premultiplied,
+    Custom { color: wgpu::BlendState, alpha: wgpu::BlendState },
+}
+
+impl Blend {
+    ///The color and alpha blend states of the preset.
+    pub fn states(&self) -> (wgpu::BlendState, wgpu::BlendState) {
+        fn state(src_factor: wgpu::BlendFactor, dst_factor: wgpu::BlendFactor) -> wgpu::BlendState {
+            wgpu::BlendState { src_factor, dst_factor, operation: wgpu::BlendOperation::Add }
+        }
+        use wgpu::BlendFactor::*;
+        match self {
+            Blend::Replace => (wgpu::BlendState::REPLACE, wgpu::BlendState::REPLACE),
+            Blend::Alpha => (state(SrcAlpha, OneMinusSrcAlpha), state(One, OneMinusSrcAlpha)),
+            Blend::Additive => (state(SrcAlpha, One), state(One, One)),
+            Blend::Premultiplied => (state(One, OneMinusSrcAlpha), state(One, OneMinusSrcAlpha)),
+            Blend::Custom { color, alpha } => (color.clone(), alpha.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DepthTest {
+    pub write: bool,
+    pub compare: wgpu::CompareFunction,
+}
+
+///Everything about a pipeline that can be compared, which with the cache key makes up its place in the cache.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Settings {
+    format: wgpu::TextureFormat,
+    blend: Blend,
+    write_mask: wgpu::ColorWrite,
+    topology: wgpu::PrimitiveTopology,
+    strip_index_format: Option<wgpu::IndexFormat>,
+    front_face: wgpu::FrontFace,
+    cull_mode: wgpu::CullMode,
+    polygon_mode: wgpu::PolygonMode,
+    depth_format: wgpu::TextureFormat,
+    depth: Option<DepthTest>,
+    sample_count: u32,
+}
+
+impl Settings {
+    fn new(format: wgpu::TextureFormat, depth_format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        Self {
+            format,
+            blend: Blend::Replace,
+            write_mask: wgpu::ColorWrite::ALL,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: wgpu::CullMode::Back,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            depth_format,
+            depth: Some(DepthTest {
+                write: true,
+                compare: wgpu::CompareFunction::Less,
+            }),
+            sample_count,
+        }
+    }
+}
+
+///Starts from an opaque, back face culled, depth tested triangle list drawing into the frame,
+///every other setting is a `with_` call away.
+#[derive(Clone)]
+pub struct PipelineBuilder<'a> {
+    pub label: Option<&'a str>,
+    vertex: (&'a wgpu::ShaderModule, &'a str),
+    fragment: Option<(&'a wgpu::ShaderModule, &'a str)>,
+    bind_group_layouts: Vec<&'a wgpu::BindGroupLayout>,
+    buffers: Vec<wgpu::VertexBufferLayout<'a>>,
+    cache_key: Option<String>,
+    settings: Settings,
+}
+
+impl<'a> PipelineBuilder<'a> {
+    ///Takes the frame and depth formats and the sample count from `gpu`.
+    pub fn new(gpu: &gpu::State, vertex: (&'a wgpu::ShaderModule, &'a str),
+        fragment: (&'a wgpu::ShaderModule, &'a str)) -> Self
+    {
+        Self {
+            label: None,
+            vertex,
+            fragment: Some(fragment),
+            bind_group_layouts: Vec::new(),
+            buffers: Vec::new(),
+            cache_key: None,
+            settings: Settings::new(gpu.sc_desc.format, gpu.settings.depth_format, gpu.settings.sample_count),
+        }
+    }
+
+    ///No fragment stage or color target, only depth is written, like a shadow map.
+    pub fn depth_only(gpu: &gpu::State, vertex: (&'a wgpu::ShaderModule, &'a str)) -> Self {
+        let mut builder = Self::new(gpu, vertex, vertex);
+        builder.fragment = None;
+        builder
+    }
+
+    pub fn with_label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    ///Lets `PipelineCache` share the pipeline. Modules and layouts can't be compared, so the key stands in
+    ///for them: builders with the same key must use the same shader modules and bind group layouts, or they
+    ///get each other's pipelines. Entry points and everything else set on the builder are told apart anyway.
+    pub fn with_cache_key(mut self, key: &str) -> Self {
+        self.cache_key = Some(key.to_string());
+        self
+    }
+
+    ///Bind group layouts in group order.
+    pub fn with_bind_group_layouts(mut self, layouts: &[&'a wgpu::BindGroupLayout]) -> Self {
+        self.bind_group_layouts = layouts.to_vec();
+        self
+    }
+
+    pub fn with_vertex_buffers(mut self, buffers: &[wgpu::VertexBufferLayout<'a>]) -> Self {
+        self.buffers = buffers.to_vec();
+        self
+    }
+
+    ///Format of the color target, for drawing into a graph texture instead of the frame.
+    pub fn with_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.settings.format = format;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: Blend) -> Self {
+        self.settings.blend = blend;
+        self
+    }
+
+    pub fn with_write_mask(mut self, write_mask: wgpu::ColorWrite) -> Self {
+        self.settings.write_mask = write_mask;
+        self
+    }
+
+    pub fn with_topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.settings.topology = topology;
+        self
+    }
+
+    ///Needed with strip topologies when drawing indexed.
+    pub fn with_strip_index_format(mut self, format: wgpu::IndexFormat) -> Self {
+        self.settings.strip_index_format = Some(format);
+        self
+    }
+
+    pub fn with_front_face(mut self, front_face: wgpu::FrontFace) -> Self {
+        self.settings.front_face = front_face;
+        self
+    }
+
+    pub fn with_cull_mode(mut self, cull_mode: wgpu::CullMode) -> Self {
+        self.settings.cull_mode = cull_mode;
+        self
+    }
+
+    ///Anything but `Fill` needs `Features::NON_FILL_POLYGON_MODE`.
+    pub fn with_polygon_mode(mut self, polygon_mode: wgpu::PolygonMode) -> Self {
+        self.settings.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn with_depth(mut self, write: bool, compare: wgpu::CompareFunction) -> Self {
+        self.settings.depth = Some(DepthTest { write, compare });
+        self
+    }
+
+    ///Depth format of the target, for drawing into a graph texture instead of the state's depth buffer.
+    pub fn with_depth_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.settings.depth_format = format;
+        self
+    }
+
+    ///For passes without a depth attachment.
+    pub fn without_depth(mut self) -> Self {
+        self.settings.depth = None;
+        self
+    }
+
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.settings.sample_count = sample_count;
+        self
+    }
+
+    pub fn build(&self, device: &wgpu::Device) -> wgpu::RenderPipeline {
+        let settings = &self.settings;
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: self.label,
+            bind_group_layouts: &self.bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+        let (color_blend, alpha_blend) = settings.blend.states();
+        let targets = [wgpu::ColorTargetState {
+            format: settings.format,
+            alpha_blend,
+            color_blend,
+            write_mask: settings.write_mask,
+        }];
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: self.label,
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: self.vertex.0,
+                entry_point: self.vertex.1,
+                buffers: &self.buffers,
+            },
+            fragment: self.fragment.map(|(module, entry_point)| wgpu::FragmentState {
+                module,
+                entry_point,
+                targets: &targets,
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: settings.topology,
+                strip_index_format: settings.strip_index_format,
+                front_face: settings.front_face,
+                cull_mode: settings.cull_mode,
+                polygon_mode: settings.polygon_mode,
+            },
+            depth_stencil: settings.depth.map(|depth| wgpu::DepthStencilState {
+                format: settings.depth_format,
+                depth_write_enabled: depth.write,
+                depth_compare: depth.compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+                clamp_depth: false,
+            }),
+            multisample: wgpu::MultisampleState {
+                count: settings.sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        })
+    }
+
+    ///`None` without a cache key, such pipelines aren't shared.
+    fn key(&self) -> Option<PipelineKey> {
+        let cache_key = self.cache_key.clone()?;
+        let entry_points = (self.vertex.1, self.fragment.map(|(_, entry)| entry));
+        Some(PipelineKey::new(cache_key, entry_points, self.bind_group_layouts.len(), &self.buffers,
+            self.settings.clone()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    cache_key: String,
+    entry_points: (String, Option<String>),
+    layouts: usize,
+    buffers: Vec<(wgpu::BufferAddress, wgpu::InputStepMode, Vec<wgpu::VertexAttribute>)>,
+    settings: Settings,
+}
+
+impl PipelineKey {
+    fn new(cache_key: String, entry_points: (&str, Option<&str>), layouts: usize,
+        buffers: &[wgpu::VertexBufferLayout], settings: Settings) -> Self
+    {
+        Self {
+            cache_key,
+            entry_points: (entry_points.0.to_string(), entry_points.1.map(str::to_string)),
+            layouts,
+            buffers: buffers.iter()
+                .map(|buffer| (buffer.array_stride, buffer.step_mode, buffer.attributes.to_vec()))
+                .collect(),
+            settings,
+        }
+    }
+}
+
+///Pipelines by builder, so effects asking for the same pipeline share one.
+///Only builders given a `with_cache_key` are shared, the rest are built every time, see the module docs
+///for what a key has to guarantee.
+///`gpu::State` clears the cache when shaders reload, since a key then names different modules.
+#[derive(Default)]
+pub struct PipelineCache {
+    pipelines: Mutex<HashMap<PipelineKey, Arc<wgpu::RenderPipeline>>>,
+}
+
+impl PipelineCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_or_build(&self, device: &wgpu::Device, builder: &PipelineBuilder) -> Arc<wgpu::RenderPipeline> {
+        let key = match builder.key() {
+            Some(key) => key,
+            None => return Arc::new(builder.build(device)),
+        };
+        let mut pipelines = self.pipelines.lock().expect("pipeline cache lock");
+        pipelines.entry(key).or_insert_with(|| Arc::new(builder.build(device))).clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.lock().expect("pipeline cache lock").len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.pipelines.lock().expect("pipeline cache lock").clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings::new(wgpu::TextureFormat::Bgra8UnormSrgb, wgpu::TextureFormat::Depth32Float, 1)
+    }
+
+    #[test]
+    fn keys_match_only_the_same_pipeline() {
+        let attributes = wgpu::vertex_attr_array![0 => Float3, 1 => Float3];
+        let buffers = [wgpu::VertexBufferLayout {
+            array_stride: 24,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &attributes,
+        }];
+        let key = |name: &str, buffers: &[wgpu::VertexBufferLayout], settings| {
+            PipelineKey::new(name.to_string(), ("vs_main", Some("fs_main")), 1, buffers, settings)
+        };
+        assert_eq!(key("mesh.wgsl", &buffers, settings()), key("mesh.wgsl", &buffers, settings()));
+
+        assert_ne!(key("mesh.wgsl", &buffers, settings()), key("basic.wgsl", &buffers, settings()));
+        assert_ne!(key("mesh.wgsl", &buffers, settings()), key("mesh.wgsl", &[], settings()));
+        let mesh = |entry_points, layouts| {
+            PipelineKey::new("mesh.wgsl".to_string(), entry_points, layouts, &buffers, settings())
+        };
+        assert_eq!(mesh(("vs_main", Some("fs_main")), 1), key("mesh.wgsl", &buffers, settings()));
+        assert_ne!(mesh(("vs_main", Some("fs_main")), 1), mesh(("vs_main", Some("fs_outline")), 1));
+        assert_ne!(mesh(("vs_main", Some("fs_main")), 1), mesh(("vs_main", None), 1));
+        assert_ne!(mesh(("vs_main", Some("fs_main")), 1), mesh(("vs_main", Some("fs_main")), 2));
+        let mut blended = settings();
+        blended.blend = Blend::Alpha;
+        assert_ne!(key("mesh.wgsl", &buffers, settings()), key("mesh.wgsl", &buffers, blended));
+        let mut multisampled = settings();
+        multisampled.sample_count = 4;
+        assert_ne!(key("mesh.wgsl", &buffers, settings()), key("mesh.wgsl", &buffers, multisampled));
+    }
+
+    #[test]
+    fn blend_presets() {
+        use wgpu::BlendFactor::*;
+        let factors = |blend: Blend| {
+            let (color, alpha) = blend.states();
+            assert_eq!((color.operation, alpha.operation), (wgpu::BlendOperation::Add, wgpu::BlendOperation::Add));
+            ((color.src_factor, color.dst_factor), (alpha.src_factor, alpha.dst_factor))
+        };
+        assert_eq!(factors(Blend::Replace), ((One, Zero), (One, Zero)));
+        assert_eq!(factors(Blend::Alpha), ((SrcAlpha, OneMinusSrcAlpha), (One, OneMinusSrcAlpha)));
+        assert_eq!(factors(Blend::Additive), ((SrcAlpha, One), (One, One)));
+        assert_eq!(factors(Blend::Premultiplied), ((One, OneMinusSrcAlpha), (One, OneMinusSrcAlpha)));
+
+        let custom = wgpu::BlendState { src_factor: DstColor, dst_factor: Zero, operation: wgpu::BlendOperation::Add };
+        assert_eq!(Blend::Custom { color: custom.clone(), alpha: custom.clone() }.states(), (custom.clone(), custom));
+    }
+}
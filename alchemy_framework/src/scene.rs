@@ -0,0 +1,357 @@
+//!A stack of scenes run as one `App`, like a menu, the game, and a pause screen over it.
+//!Only the top scene gets updates and input, scenes below it are paused until it's popped.
+use crate::gpu;
+use crate::graphics::{App, AppWindow};
+use std::marker::PhantomData;
+use std::time::Duration;
+use winit::event_loop::ControlFlow;
+
+///What the scene stack should do after an update.
+pub enum Transition<C = AppWindow> {
+    None,
+    ///Pauses the current scene and starts this one on top of it.
+    Push(Box<dyn Scene<C>>),
+    ///Ends the current scene and resumes the one below, quitting when there's none left.
+    Pop,
+    ///Ends the current scene and starts this one in its place.
+    Switch(Box<dyn Scene<C>>),
+    Quit,
+}
+
+///`C` is what the scenes are handed, always the `AppWindow` outside of tests.
+pub trait Scene<C = AppWindow> {
+    fn on_update(&mut self, app: &mut C, delta: Duration) -> Transition<C>;
+
+    fn on_draw(&self, app: &mut C, control_flow: &mut ControlFlow, alpha: f32);
+
+    ///Called when the scene is put on the stack.
+    fn on_enter(&mut self, _app: &mut C) {}
+
+    ///Called when the scene is taken off the stack.
+    fn on_exit(&mut self, _app: &mut C) {}
+
+    ///Called when another scene is pushed on top of this one.
+    fn on_pause(&mut self, _app: &mut C) {}
+
+    ///Called when the scene on top of this one is popped.
+    fn on_resume(&mut self, _app: &mut C) {}
+
+    fn on_input(&mut self, _event: &winit::event::DeviceEvent) {}
+
+    fn on_window_event(&mut self, _event: &winit::event::WindowEvent) {}
+
+    ///Every scene on the stack is resized, not just the top one, so they're right when resumed.
+    fn on_resize(&mut self, _physical_size: winit::dpi::PhysicalSize<u32>) {}
+
+    ///Called for every scene still on the stack when the program ends, top first.
+    fn on_quit(&self) {}
+
+    ///Overlays have the scene below them drawn first, like a pause menu over the paused game.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+///The scene a `SceneApp` starts with.
+pub trait InitialScene: Scene + 'static {
+    fn new(gpu: &gpu::State) -> Self;
+}
+
+///The stack itself, kept apart from `SceneApp` so it doesn't need a window to run.
+struct SceneStack<C> {
+    stack: Vec<Box<dyn Scene<C>>>,
+    quit: bool,
+}
+
+impl<C> SceneStack<C> {
+    fn new(initial: Box<dyn Scene<C>>) -> Self {
+        Self {
+            stack: vec![initial],
+            quit: false,
+        }
+    }
+
+    fn enter(&mut self, app: &mut C) {
+        for scene in &mut self.stack {
+            scene.on_enter(app);
+        }
+    }
+
+    fn update(&mut self, app: &mut C, delta: Duration) {
+        //With a fixed timestep this runs several times a frame, nothing more happens once quitting.
+        if self.quit {
+            return;
+        }
+        let transition = match self.stack.last_mut() {
+            Some(top) => top.on_update(app, delta),
+            None => return,
+        };
+        self.apply(transition, app);
+    }
+
+    fn apply(&mut self, transition: Transition<C>, app: &mut C) {
+        match transition {
+            Transition::None => {}
+            Transition::Push(mut scene) => {
+                if let Some(top) = self.stack.last_mut() {
+                    top.on_pause(app);
+                }
+                scene.on_enter(app);
+                self.stack.push(scene);
+            }
+            Transition::Pop => {
+                if let Some(mut top) = self.stack.pop() {
+                    top.on_exit(app);
+                }
+                match self.stack.last_mut() {
+                    Some(top) => top.on_resume(app),
+                    None => self.quit = true,
+                }
+            }
+            Transition::Switch(mut scene) => {
+                if let Some(mut top) = self.stack.pop() {
+                    top.on_exit(app);
+                }
+                scene.on_enter(app);
+                self.stack.push(scene);
+            }
+            Transition::Quit => self.quit = true,
+        }
+    }
+
+    fn draw(&self, app: &mut C, control_flow: &mut ControlFlow, alpha: f32) {
+        if self.quit {
+            *control_flow = ControlFlow::Exit;
+            return;
+        }
+        //Walk down past overlays to the first scene that draws everything, then draw upwards from it.
+        let mut bottom = self.stack.len();
+        while bottom > 0 {
+            bottom -= 1;
+            if !self.stack[bottom].is_overlay() {
+                break;
+            }
+        }
+        for scene in &self.stack[bottom..] {
+            scene.on_draw(app, control_flow, alpha);
+        }
+    }
+
+    fn input(&mut self, event: &winit::event::DeviceEvent) {
+        if let Some(top) = self.stack.last_mut() {
+            top.on_input(event);
+        }
+    }
+
+    fn window_event(&mut self, event: &winit::event::WindowEvent) {
+        if let Some(top) = self.stack.last_mut() {
+            top.on_window_event(event);
+        }
+    }
+
+    fn resize(&mut self, physical_size: winit::dpi::PhysicalSize<u32>) {
+        for scene in &mut self.stack {
+            scene.on_resize(physical_size);
+        }
+    }
+
+    fn quit(&self) {
+        for scene in self.stack.iter().rev() {
+            scene.on_quit();
+        }
+    }
+}
+
+///Runs a stack of scenes starting with `I`, started like any other app with `SceneApp::<I>::run(config)`.
+pub struct SceneApp<I: InitialScene> {
+    scenes: SceneStack<AppWindow>,
+    _initial: PhantomData<I>,
+}
+
+impl<I: InitialScene> App for SceneApp<I> {
+    fn new(gpu: &gpu::State) -> Self {
+        Self {
+            scenes: SceneStack::new(Box::new(I::new(gpu))),
+            _initial: PhantomData,
+        }
+    }
+
+    fn on_load(&mut self, app: &mut AppWindow) {
+        self.scenes.enter(app);
+    }
+
+    fn on_update(&mut self, app: &mut AppWindow, delta: Duration) {
+        self.scenes.update(app, delta);
+    }
+
+    fn on_draw(&self, app: &mut AppWindow, control_flow: &mut ControlFlow, alpha: f32) {
+        self.scenes.draw(app, control_flow, alpha);
+    }
+
+    fn on_input(&mut self, event: &winit::event::DeviceEvent) {
+        self.scenes.input(event);
+    }
+
+    fn on_window_event(&mut self, event: &winit::event::WindowEvent) {
+        self.scenes.window_event(event);
+    }
+
+    fn on_resize(&mut self, physical_size: winit::dpi::PhysicalSize<u32>) {
+        self.scenes.resize(physical_size);
+    }
+
+    fn on_exit(&self) {
+        self.scenes.quit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    ///Logs every call and plays back the transitions it's given, one per update.
+    struct Logged {
+        name: &'static str,
+        log: Log,
+        overlay: bool,
+        transitions: Vec<Transition<()>>,
+    }
+
+    impl Logged {
+        fn new(name: &'static str, log: &Log) -> Self {
+            Self { name, log: log.clone(), overlay: false, transitions: Vec::new() }
+        }
+
+        fn overlay(mut self) -> Self {
+            self.overlay = true;
+            self
+        }
+
+        fn then(mut self, transition: Transition<()>) -> Self {
+            self.transitions.push(transition);
+            self
+        }
+
+        fn record(&self, call: &str) {
+            self.log.borrow_mut().push(format!("{} {}", call, self.name));
+        }
+    }
+
+    impl Scene<()> for Logged {
+        fn on_update(&mut self, _app: &mut (), _delta: Duration) -> Transition<()> {
+            self.record("update");
+            if self.transitions.is_empty() {
+                Transition::None
+            } else {
+                self.transitions.remove(0)
+            }
+        }
+
+        fn on_draw(&self, _app: &mut (), _control_flow: &mut ControlFlow, _alpha: f32) {
+            self.record("draw");
+        }
+
+        fn on_enter(&mut self, _app: &mut ()) {
+            self.record("enter");
+        }
+
+        fn on_exit(&mut self, _app: &mut ()) {
+            self.record("exit");
+        }
+
+        fn on_pause(&mut self, _app: &mut ()) {
+            self.record("pause");
+        }
+
+        fn on_resume(&mut self, _app: &mut ()) {
+            self.record("resume");
+        }
+
+        fn on_input(&mut self, _event: &winit::event::DeviceEvent) {
+            self.record("input");
+        }
+
+        fn on_quit(&self) {
+            self.record("quit");
+        }
+
+        fn is_overlay(&self) -> bool {
+            self.overlay
+        }
+    }
+
+    fn take(log: &Log) -> Vec<String> {
+        log.borrow_mut().drain(..).collect()
+    }
+
+    const DELTA: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn transitions_move_through_the_stack() {
+        let log = Log::default();
+        let pause = Logged::new("pause", &log).then(Transition::Switch(Box::new(Logged::new("options", &log)
+            .then(Transition::Pop))));
+        let game = Logged::new("game", &log).then(Transition::Push(Box::new(pause))).then(Transition::None)
+            .then(Transition::Pop);
+        let mut stack = SceneStack::new(Box::new(game));
+        stack.enter(&mut ());
+        assert_eq!(take(&log), ["enter game"]);
+
+        stack.update(&mut (), DELTA);
+        assert_eq!(take(&log), ["update game", "pause game", "enter pause"]);
+        stack.update(&mut (), DELTA);
+        assert_eq!(take(&log), ["update pause", "exit pause", "enter options"]);
+        stack.update(&mut (), DELTA);
+        assert_eq!(take(&log), ["update options", "exit options", "resume game"]);
+        stack.update(&mut (), DELTA);
+        assert_eq!(take(&log), ["update game"]);
+
+        //Popping the last scene quits, after which updates stop and the next draw exits.
+        stack.update(&mut (), DELTA);
+        assert_eq!(take(&log), ["update game", "exit game"]);
+        assert!(stack.quit && stack.stack.is_empty());
+        stack.update(&mut (), DELTA);
+        let mut control_flow = ControlFlow::Poll;
+        stack.draw(&mut (), &mut control_flow, 1.0);
+        assert_eq!(control_flow, ControlFlow::Exit);
+        assert!(take(&log).is_empty());
+    }
+
+    #[test]
+    fn quit_keeps_the_stack_for_on_quit() {
+        let log = Log::default();
+        let mut stack = SceneStack::new(Box::new(Logged::new("game", &log)
+            .then(Transition::Push(Box::new(Logged::new("menu", &log).then(Transition::Quit))))));
+        stack.update(&mut (), DELTA);
+        stack.update(&mut (), DELTA);
+        take(&log);
+        assert!(stack.quit);
+
+        let mut control_flow = ControlFlow::Poll;
+        stack.draw(&mut (), &mut control_flow, 1.0);
+        assert_eq!(control_flow, ControlFlow::Exit);
+        stack.quit();
+        assert_eq!(take(&log), ["quit menu", "quit game"]);
+    }
+
+    #[test]
+    fn overlays_draw_over_the_scene_below_and_only_the_top_gets_input() {
+        let log = Log::default();
+        let mut stack = SceneStack::new(Box::new(Logged::new("title", &log)));
+        stack.apply(Transition::Push(Box::new(Logged::new("game", &log))), &mut ());
+        stack.apply(Transition::Push(Box::new(Logged::new("hud", &log).overlay())), &mut ());
+        stack.apply(Transition::Push(Box::new(Logged::new("pause", &log).overlay())), &mut ());
+        take(&log);
+
+        stack.draw(&mut (), &mut ControlFlow::Poll, 1.0);
+        assert_eq!(take(&log), ["draw game", "draw hud", "draw pause"]);
+
+        stack.input(&winit::event::DeviceEvent::Added);
+        stack.update(&mut (), DELTA);
+        assert_eq!(take(&log), ["input pause", "update pause"]);
+    }
+}
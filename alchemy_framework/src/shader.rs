@@ -0,0 +1,304 @@
+//!Shaders loaded from WGSL or GLSL source at runtime, checked with naga before the device sees them
+//!so a mistake comes back as an `Error::Shader` with its line instead of a device panic.
+use crate::Error;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+#[allow(unused_imports)]
+use log::{error, warn, info, debug, trace};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    fn to_naga(self) -> naga::ShaderStage {
+        match self {
+            ShaderStage::Vertex => naga::ShaderStage::Vertex,
+            ShaderStage::Fragment => naga::ShaderStage::Fragment,
+            ShaderStage::Compute => naga::ShaderStage::Compute,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderLanguage {
+    ///Any number of entry points, picked by name when building a pipeline.
+    Wgsl,
+    ///One stage per source with `main` as the entry point, translated to SPIR-V for the device.
+    Glsl(ShaderStage),
+}
+
+#[derive(Debug, Clone)]
+pub struct Shader {
+    ///Used in errors and as the module's label, the path when loaded from a file.
+    pub name: String,
+    pub language: ShaderLanguage,
+    pub source: String,
+}
+
+impl Shader {
+    pub fn wgsl(name: &str, source: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            language: ShaderLanguage::Wgsl,
+            source: source.to_string(),
+        }
+    }
+
+    pub fn glsl(name: &str, source: &str, stage: ShaderStage) -> Self {
+        Self {
+            name: name.to_string(),
+            language: ShaderLanguage::Glsl(stage),
+            source: source.to_string(),
+        }
+    }
+
+    ///Reads a shader, the language comes from the extension: `.wgsl`, or `.vert`, `.frag` and `.comp` for GLSL.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let language = match path.extension().and_then(|e| e.to_str()) {
+            Some("wgsl") => ShaderLanguage::Wgsl,
+            Some("vert") => ShaderLanguage::Glsl(ShaderStage::Vertex),
+            Some("frag") => ShaderLanguage::Glsl(ShaderStage::Fragment),
+            Some("comp") => ShaderLanguage::Glsl(ShaderStage::Compute),
+            _ => return Err(Error::Shader {
+                name,
+                line: None,
+                message: "unknown shader extension, expected .wgsl, .vert, .frag or .comp".to_string(),
+            }),
+        };
+        let source = std::fs::read_to_string(path)?;
+        Ok(Self { name, language, source })
+    }
+
+    fn error(&self, line: Option<usize>, message: String) -> Error {
+        Error::Shader { name: self.name.clone(), line, message }
+    }
+
+    ///Parses and validates the source without needing a device.
+    pub fn validate(&self) -> Result<naga::Module, Error> {
+        let module = match self.language {
+            ShaderLanguage::Wgsl => naga::front::wgsl::parse_str(&self.source)
+                .map_err(|e| self.error(Some(e.line), e.error.to_string()))?,
+            ShaderLanguage::Glsl(stage) => {
+                naga::front::glsl::parse_str(&self.source, "main", stage.to_naga(), Default::default())
+                    .map_err(|e| self.error(glsl_error_line(&e), e.kind.to_string()))?
+            }
+        };
+        naga::proc::Validator::new().validate(&module).map_err(|e| self.error(None, format!("{:?}", e)))?;
+        Ok(module)
+    }
+
+    ///Validates the source and creates the module, nothing reaches the device if it doesn't validate.
+    pub fn create_module(&self, device: &wgpu::Device) -> Result<wgpu::ShaderModule, Error> {
+        let module = self.validate()?;
+        let source = match self.language {
+            ShaderLanguage::Wgsl => wgpu::ShaderSource::Wgsl(self.source.as_str().into()),
+            ShaderLanguage::Glsl(_) => {
+                let mut capabilities = naga::FastHashSet::default();
+                capabilities.insert(naga::back::spv::Capability::Shader);
+                let words = naga::back::spv::write_vec(&module, naga::back::spv::WriterFlags::NONE, capabilities)
+                    .map_err(|e| self.error(None, format!("{:?}", e)))?;
+                wgpu::ShaderSource::SpirV(words.into())
+            }
+        };
+        Ok(device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some(self.name.as_str()),
+            source,
+            flags: wgpu::ShaderFlags::all(),
+        }))
+    }
+}
+
+struct CachedShader<M> {
+    shader: Shader,
+    module: M,
+}
+
+///The loaded shaders and their reloading, apart from the device so it runs on naga modules in tests.
+struct Modules<M> {
+    shaders: HashMap<PathBuf, CachedShader<M>>,
+}
+
+impl<M> Modules<M> {
+    fn new() -> Self {
+        Self { shaders: HashMap::new() }
+    }
+
+    ///`path` must already be canonical.
+    fn load<F: Fn(&Shader) -> Result<M, Error>>(&mut self, path: &Path, compile: F) -> Result<&M, Error> {
+        if !self.shaders.contains_key(path) {
+            let shader = Shader::load(path)?;
+            let module = compile(&shader)?;
+            self.shaders.insert(path.to_path_buf(), CachedShader { shader, module });
+        }
+        Ok(&self.shaders[path].module)
+    }
+
+    fn get(&self, path: &Path) -> Option<&M> {
+        self.shaders.get(path).map(|cached| &cached.module)
+    }
+
+    fn poll<F: Fn(&Shader) -> Result<M, Error>>(&mut self, events: &Receiver<notify::DebouncedEvent>, compile: F)
+        -> Vec<PathBuf>
+    {
+        let mut changed = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            let path = match event {
+                notify::DebouncedEvent::Write(path)
+                | notify::DebouncedEvent::Create(path)
+                | notify::DebouncedEvent::Rename(_, path) => path,
+                notify::DebouncedEvent::Error(e, _) => {
+                    warn!("shader watcher: {}", e);
+                    continue;
+                }
+                _ => continue,
+            };
+            let path = path.canonicalize().unwrap_or(path);
+            let cached = match self.shaders.get_mut(&path) {
+                Some(cached) => cached,
+                None => continue,
+            };
+            let reloaded = Shader::load(&path).and_then(|shader| {
+                let module = compile(&shader)?;
+                Ok((shader, module))
+            });
+            match reloaded {
+                Ok((shader, module)) => {
+                    info!("reloaded shader {}", path.display());
+                    *cached = CachedShader { shader, module };
+                    if !changed.contains(&path) {
+                        changed.push(path);
+                    }
+                }
+                Err(e) => error!("{}, keeping the last good version of {}", e, cached.shader.name),
+            }
+        }
+        changed
+    }
+}
+
+///Shader modules loaded from files, by path. When watching, changed files are recompiled by `poll`,
+///a file that no longer compiles logs its error and keeps its last good module.
+pub struct ShaderCache {
+    modules: Modules<wgpu::ShaderModule>,
+    watcher: Option<notify::RecommendedWatcher>,
+    events: Receiver<notify::DebouncedEvent>,
+}
+
+impl ShaderCache {
+    ///`watch` turns on hot reloading, meant for development builds.
+    pub fn new(watch: bool) -> Result<Self, Error> {
+        let (sender, events) = channel();
+        let watcher = if watch {
+            Some(notify::watcher(sender, Duration::from_millis(100))?)
+        } else {
+            None
+        };
+        Ok(Self {
+            modules: Modules::new(),
+            watcher,
+            events,
+        })
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    ///Loads and compiles the shader at `path`, or returns the module already loaded from it.
+    pub fn load<P: AsRef<Path>>(&mut self, device: &wgpu::Device, path: P) -> Result<&wgpu::ShaderModule, Error> {
+        let path = path.as_ref().canonicalize()?;
+        if self.modules.get(&path).is_none() {
+            //Editors often save by replacing the file, so the directory is watched rather than the file.
+            if let (Some(watcher), Some(dir)) = (&mut self.watcher, path.parent()) {
+                use notify::Watcher;
+                watcher.watch(dir, notify::RecursiveMode::NonRecursive)?;
+            }
+        }
+        self.modules.load(&path, |shader| shader.create_module(device))
+    }
+
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&wgpu::ShaderModule> {
+        let path = path.as_ref().canonicalize().ok()?;
+        self.modules.get(&path)
+    }
+
+    ///Recompiles shaders whose files changed, returning the paths that now have a new module.
+    pub fn poll(&mut self, device: &wgpu::Device) -> Vec<PathBuf> {
+        self.modules.poll(&self.events, |shader| shader.create_module(device))
+    }
+}
+
+///naga 0.3 keeps its GLSL error kinds and tokens in private modules so they can't be matched on,
+///the line is read from the `TokenMetadata` the failing token carries instead, counted from line zero.
+fn glsl_error_line(error: &naga::front::glsl::ParseError) -> Option<usize> {
+    const FIELD: &str = "TokenMetadata { line: ";
+    let message = format!("{:?}", error.kind);
+    let start = message.find(FIELD)? + FIELD.len();
+    let digits: String = message[start..].chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse::<usize>().ok().map(|line| line + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_failing_line() {
+        let shader = Shader::wgsl("broken.wgsl", "[[stage(vertex)]]\nfn vs_main() {\n    const x: f32 = 1.0\n}\n");
+        match shader.validate() {
+            Err(Error::Shader { line, .. }) => assert_eq!(line, Some(4)),
+            other => panic!("expected a shader error, got {:?}", other.map(|_| ())),
+        }
+
+        let shader = Shader::glsl("broken.frag",
+            "#version 450\nlayout(location=0) out vec4 f_color;\nvoid main() {\n    f_color = vec4(0.3 1.0);\n}\n",
+            ShaderStage::Fragment);
+        match shader.validate() {
+            Err(Error::Shader { line, .. }) => assert_eq!(line, Some(4)),
+            other => panic!("expected a shader error, got {:?}", other.map(|_| ())),
+        }
+
+        for source in &[include_str!("basic.wgsl"), include_str!("mesh.wgsl"), include_str!("skybox.wgsl")] {
+            Shader::wgsl("built in", source).validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn poll_reloads_changed_files_and_keeps_the_last_good_module() {
+        let dir = std::env::temp_dir().join(format!("alchemy_shaders_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("effect.wgsl");
+        std::fs::write(&path, include_str!("basic.wgsl")).unwrap();
+        let path = path.canonicalize().unwrap();
+
+        let mut modules = Modules::new();
+        let compile = |shader: &Shader| shader.validate();
+        let has_entry_point = |modules: &Modules<naga::Module>, name: &str| {
+            modules.get(&path).unwrap().entry_points.keys().any(|(_, entry)| entry == name)
+        };
+        modules.load(&path, compile).unwrap();
+        assert!(has_entry_point(&modules, "fs_main"));
+
+        let (sender, events) = channel();
+        std::fs::write(&path, include_str!("skybox.wgsl").replace("fs_main", "fs_other")).unwrap();
+        sender.send(notify::DebouncedEvent::Write(path.clone())).unwrap();
+        sender.send(notify::DebouncedEvent::Write(dir.join("unrelated.wgsl"))).unwrap();
+        assert_eq!(modules.poll(&events, compile), vec![path.clone()]);
+        assert!(has_entry_point(&modules, "fs_other"));
+
+        std::fs::write(&path, "fn broken(").unwrap();
+        sender.send(notify::DebouncedEvent::Write(path.clone())).unwrap();
+        assert!(modules.poll(&events, compile).is_empty());
+        assert!(has_entry_point(&modules, "fs_other"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
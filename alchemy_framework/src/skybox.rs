@@ -0,0 +1,115 @@
+//!A cube map drawn behind everything in the main pass, set with `gpu::Background::Skybox`.
+use crate::camera::{Camera, CameraProjection};
+use crate::gpu;
+use crate::pipeline::PipelineBuilder;
+use crate::shader::Shader;
+use crate::texture;
+use cgmath::{SquareMatrix, Vector4};
+use wgpu::util::DeviceExt;
+
+pub struct Skybox {
+    pub cubemap: texture::Texture,
+    render_pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Skybox {
+    ///`cubemap` should come from `Texture::cube_from_images` or `Texture::load_cube`.
+    pub fn new(gpu: &gpu::State, cubemap: texture::Texture) -> Self {
+        let layout = gpu.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("skybox_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let identity: [[f32; 4]; 4] = cgmath::Matrix4::<f32>::identity().into();
+        let uniform_buffer = gpu.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Skybox Buffer"),
+            contents: bytemuck::cast_slice(&[identity]),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("skybox_bind_group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&cubemap.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&cubemap.sampler),
+                },
+            ],
+        });
+
+        let module = Shader::wgsl("skybox.wgsl", include_str!("skybox.wgsl")).create_module(&gpu.device)
+            .expect("built in shader validates");
+
+        //Drawn on the far plane without writing depth, so anything drawn after covers it.
+        let render_pipeline = PipelineBuilder::new(gpu, (&module, "vs_main"), (&module, "fs_main"))
+            .with_label("Skybox Pipeline")
+            .with_bind_group_layouts(&[&layout])
+            .with_cull_mode(wgpu::CullMode::None)
+            .with_depth(false, wgpu::CompareFunction::LessEqual)
+            .build(&gpu.device);
+
+        Self {
+            cubemap,
+            render_pipeline,
+            uniform_buffer,
+            bind_group,
+        }
+    }
+
+    ///Points the skybox the way the camera looks, call it whenever the camera moves.
+    pub fn update<P: CameraProjection + ?Sized>(&self, queue: &wgpu::Queue, camera: &Camera, projection: &P) {
+        //Only the rotation matters, the sky is infinitely far away.
+        let mut view = camera.calc_matrix();
+        view.w = Vector4::new(0.0, 0.0, 0.0, 1.0);
+        let inv_view_proj = (projection.calc_matrix() * view).invert().unwrap_or_else(cgmath::Matrix4::identity);
+        let inv_view_proj: [[f32; 4]; 4] = inv_view_proj.into();
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[inv_view_proj]));
+    }
+
+    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,33 @@
+[[block]]
+struct Uniforms {
+    inv_view_proj: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> u_sky: Uniforms;
+[[group(0), binding(1)]]
+var t_sky: texture_cube<f32>;
+[[group(0), binding(2)]]
+var s_sky: sampler;
+
+[[builtin(vertex_index)]] var<in> in_vertex_index: u32;
+[[location(0)]] var<out> out_direction_vs: vec3<f32>;
+[[builtin(position)]] var<out> out_position: vec4<f32>;
+
+[[stage(vertex)]]
+fn vs_main() {
+    //One triangle covering the screen, sitting on the far plane.
+    const x: f32 = f32(i32(in_vertex_index) / 2) * 4.0 - 1.0;
+    const y: f32 = f32(i32(in_vertex_index) & 1) * 4.0 - 1.0;
+    const position: vec4<f32> = vec4<f32>(x, y, 1.0, 1.0);
+    const world: vec4<f32> = u_sky.inv_view_proj * position;
+    out_direction_vs = world.xyz / world.w;
+    out_position = position;
+}
+
+[[location(0)]] var<in> in_direction_fs: vec3<f32>;
+[[location(0)]] var<out> out_color_fs: vec4<f32>;
+
+[[stage(fragment)]]
+fn fs_main() {
+    out_color_fs = textureSample(t_sky, s_sky, in_direction_fs);
+}
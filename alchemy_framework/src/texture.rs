@@ -20,8 +20,83 @@ impl Texture {
         Self::from_image(device, queue, &img, label, is_normal_map)
     }
 
+    ///Same as `load` with the full mip chain, so the texture doesn't shimmer when seen from afar.
+    pub fn load_with_mipmaps<P: AsRef<Path>>(device: &wgpu::Device, queue: &wgpu::Queue, path: P,
+        is_normal_map: bool,) -> Result<Self, image::error::ImageError> {
+        let path_copy = path.as_ref().to_path_buf();
+        let label = path_copy.to_str();
+
+        let img = image::open(path)?;
+        Self::from_image_with_mipmaps(device, queue, &img, label, is_normal_map)
+    }
+
+    ///Mip levels down to 1x1 for a texture of this size.
+    pub fn mip_level_count(width: u32, height: u32) -> u32 {
+        32 - width.max(height).max(1).leading_zeros()
+    }
+
+    ///Each level half the size of the one before, down to 1x1, starting with `base` itself.
+    ///Colors are averaged in linear space when `srgb`, otherwise the levels darken as they shrink.
+    fn mip_chain(base: image::RgbaImage, levels: u32, srgb: bool) -> Vec<image::RgbaImage> {
+        if levels <= 1 {
+            return vec![base];
+        }
+        let decode = |value: u8, channel: usize| {
+            let value = value as f32 / 255.0;
+            if !srgb || channel == 3 {
+                return value;
+            }
+            if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+        };
+        let encode = |value: f32, channel: usize| {
+            let value = if !srgb || channel == 3 {
+                value
+            } else if value <= 0.0031308 {
+                value * 12.92
+            } else {
+                1.055 * value.powf(1.0 / 2.4) - 0.055
+            };
+            (value.clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+
+        let mut level = image::ImageBuffer::from_fn(base.width(), base.height(), |x, y| {
+            let pixel = base.get_pixel(x, y);
+            image::Rgba([decode(pixel[0], 0), decode(pixel[1], 1), decode(pixel[2], 2), decode(pixel[3], 3)])
+        });
+        let mut chain = vec![base];
+        for _ in 1..levels {
+            let width = (level.width() / 2).max(1);
+            let height = (level.height() / 2).max(1);
+            level = image::imageops::resize(&level, width, height, image::imageops::FilterType::Triangle);
+            chain.push(image::RgbaImage::from_fn(width, height, |x, y| {
+                let pixel = level.get_pixel(x, y);
+                image::Rgba([encode(pixel[0], 0), encode(pixel[1], 1), encode(pixel[2], 2), encode(pixel[3], 3)])
+            }));
+        }
+        chain
+    }
+
+    ///Whether the format is one of the depth or depth and stencil formats.
+    pub fn is_depth_format(format: wgpu::TextureFormat) -> bool {
+        matches!(format, wgpu::TextureFormat::Depth32Float
+            | wgpu::TextureFormat::Depth24Plus
+            | wgpu::TextureFormat::Depth24PlusStencil8)
+    }
+
+    pub fn has_stencil(format: wgpu::TextureFormat) -> bool {
+        format == wgpu::TextureFormat::Depth24PlusStencil8
+    }
+
     pub fn create_depth_texture(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor, 
     label: &str,) -> Self 
+    {
+        Self::create_depth_texture_with_format(device, sc_desc, Self::DEPTH_FORMAT, 1, label)
+    }
+
+    ///Depth texture in `format`, like `Depth24PlusStencil8` when a stencil buffer is needed,
+    ///multisampled to match the color target it's drawn with.
+    pub fn create_depth_texture_with_format(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor,
+    format: wgpu::TextureFormat, sample_count: u32, label: &str,) -> Self
     {
 
         let size = wgpu::Extent3d {
@@ -34,9 +109,9 @@ impl Texture {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
+            format,
             usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
         };
         let texture = device.create_texture(&desc);
@@ -62,6 +137,131 @@ impl Texture {
         }
     }
 
+    ///Color texture sized and formatted from the descriptor, used as the frame when there is no window.
+    pub fn create_render_target(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor,
+    label: &str,) -> Self
+    {
+        Self::create_multisampled_target(device, sc_desc, 1, label)
+    }
+
+    ///Color texture drawn into with `sample_count` samples and resolved into the frame.
+    pub fn create_multisampled_target(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor,
+    sample_count: u32, label: &str,) -> Self
+    {
+        let size = wgpu::Extent3d {
+            width: sc_desc.width,
+            height: sc_desc.height,
+            depth: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: sc_desc.format,
+            usage: sc_desc.usage,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    ///Loads the six faces of a cube map, in the order +X, -X, +Y, -Y, +Z, -Z.
+    pub fn load_cube<P: AsRef<Path>>(device: &wgpu::Device, queue: &wgpu::Queue, paths: [P; 6],
+        label: Option<&str>) -> Result<Self, image::error::ImageError>
+    {
+        let mut faces = Vec::with_capacity(6);
+        for path in paths.iter() {
+            faces.push(image::open(path)?);
+        }
+        Self::cube_from_images(device, queue, &faces, label)
+    }
+
+    ///Cube map from six square faces of the same size, in the order +X, -X, +Y, -Y, +Z, -Z.
+    pub fn cube_from_images(device: &wgpu::Device, queue: &wgpu::Queue, faces: &[image::DynamicImage],
+        label: Option<&str>) -> Result<Self, image::error::ImageError>
+    {
+        use image::error::{ImageError, ParameterError, ParameterErrorKind};
+
+        let dimensions = faces.first().map(|face| face.dimensions()).unwrap_or((0, 0));
+        if faces.len() != 6 || dimensions.0 != dimensions.1 || dimensions.0 == 0
+            || faces.iter().any(|face| face.dimensions() != dimensions)
+        {
+            return Err(ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::DimensionMismatch)));
+        }
+
+        let mut rgba = Vec::with_capacity((4 * dimensions.0 * dimensions.1 * 6) as usize);
+        for face in faces {
+            rgba.extend_from_slice(&face.to_rgba8());
+        }
+
+        let size = wgpu::Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth: 6,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+
+        queue.write_texture(
+            wgpu::TextureCopyView {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &rgba,
+            wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: 4 * dimensions.0,
+                rows_per_image: dimensions.1,
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
     #[allow(dead_code)]
     #[allow(dead_code)]
     pub fn from_bytes(
@@ -75,15 +275,53 @@ impl Texture {
         Self::from_image(device, queue, &img, Some(label), is_normal_map)
     }
 
+    pub fn from_bytes_with_mipmaps(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+        is_normal_map: bool,
+    ) -> Result<Self, image::error::ImageError> {
+        let img = image::load_from_memory(bytes)?;
+        Self::from_image_with_mipmaps(device, queue, &img, Some(label), is_normal_map)
+    }
+
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
         is_normal_map: bool,
+    ) -> Result<Self, image::error::ImageError> {
+        Self::create(device, queue, img, label, is_normal_map, false)
+    }
+
+    pub fn from_image_with_mipmaps(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+        is_normal_map: bool,
+    ) -> Result<Self, image::error::ImageError> {
+        Self::create(device, queue, img, label, is_normal_map, true)
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+        is_normal_map: bool,
+        generate_mipmaps: bool,
     ) -> Result<Self, image::error::ImageError> {
         let dimensions = img.dimensions();
-        let rgba = img.to_rgba8();
+        let mip_level_count = if generate_mipmaps {
+            Self::mip_level_count(dimensions.0, dimensions.1)
+        } else {
+            1
+        };
+        //Downsampled on the cpu, there's no blit to lean on.
+        let mips = Self::mip_chain(img.to_rgba8(), mip_level_count, !is_normal_map);
 
         let size = wgpu::Extent3d {
             width: dimensions.0,
@@ -93,7 +331,7 @@ impl Texture {
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: if is_normal_map {
@@ -104,31 +342,51 @@ impl Texture {
             usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
         });
 
-        queue.write_texture(
-            wgpu::TextureCopyView {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            &rgba,
-            wgpu::TextureDataLayout {
-                offset: 0,
-                bytes_per_row: 4 * dimensions.0,
-                rows_per_image: dimensions.1,
-            },
-            size,
-        );
+        for (level, mip) in mips.iter().enumerate() {
+            queue.write_texture(
+                wgpu::TextureCopyView {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                mip,
+                wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: 4 * mip.width(),
+                    rows_per_image: mip.height(),
+                },
+                wgpu::Extent3d {
+                    width: mip.width(),
+                    height: mip.height(),
+                    depth: 1,
+                },
+            );
+        }
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+        let sampler = if generate_mipmaps {
+            device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                lod_min_clamp: 0.0,
+                lod_max_clamp: mip_level_count as f32,
+                ..Default::default()
+            })
+        } else {
+            device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Nearest,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            })
+        };
 
         Ok(Self {
             texture,
@@ -185,4 +443,35 @@ impl Texture{
             label: Some("texture_bind_group_layout"),
         });
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_chain_halves_down_to_one_pixel() {
+        assert_eq!(Texture::mip_level_count(256, 64), 9);
+        assert_eq!(Texture::mip_level_count(1, 1), 1);
+
+        let levels = Texture::mip_level_count(6, 3);
+        let chain = Texture::mip_chain(image::RgbaImage::from_pixel(6, 3, image::Rgba([200, 100, 50, 255])), levels,
+            true);
+        let sizes: Vec<(u32, u32)> = chain.iter().map(|mip| mip.dimensions()).collect();
+        assert_eq!(sizes, vec![(6, 3), (3, 1), (1, 1)]);
+        assert_eq!(chain[2].get_pixel(0, 0), &image::Rgba([200, 100, 50, 255]));
+    }
+
+    #[test]
+    fn srgb_mips_average_in_linear_space() {
+        let mut checker = image::RgbaImage::from_pixel(2, 1, image::Rgba([0, 0, 0, 0]));
+        checker.put_pixel(1, 0, image::Rgba([255, 255, 255, 255]));
+
+        //Half way between black and white is 0.5 in linear light, which sRGB stores as 188.
+        let srgb = Texture::mip_chain(checker.clone(), 2, true);
+        assert_eq!(srgb[1].get_pixel(0, 0), &image::Rgba([188, 188, 188, 128]));
+        //Normal maps hold directions, not colors, and are averaged as they are.
+        let linear = Texture::mip_chain(checker, 2, false);
+        assert_eq!(linear[1].get_pixel(0, 0), &image::Rgba([128, 128, 128, 128]));
+    }
+}
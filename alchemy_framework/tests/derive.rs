@@ -0,0 +1,7 @@
+//!The derives check their layout while compiling, so the failures are tests that don't compile.
+#[test]
+fn derives() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/derive_pass.rs");
+    cases.compile_fail("tests/ui/uniform_*.rs");
+}
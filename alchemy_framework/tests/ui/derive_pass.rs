@@ -0,0 +1,33 @@
+use alchemy_framework::camera::Uniform;
+use alchemy_framework::mesh::Vertex;
+
+#[repr(C, align(16))]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Uniform)]
+#[uniform(fragment)]
+struct Light {
+    position: [f32; 3],
+    intensity: f32,
+    color: [f32; 3],
+    _padding: f32,
+    transform: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Vertex)]
+#[vertex(instance, location = 5)]
+struct Instance {
+    model: [[f32; 4]; 4],
+    tint: [f32; 3],
+}
+
+fn main() {
+    let entry = Light::layout_entry(2);
+    assert_eq!(entry.binding, 2);
+    assert_eq!(entry.visibility, alchemy_framework::wgpu::ShaderStage::FRAGMENT);
+
+    let desc = Instance::desc();
+    assert_eq!(desc.array_stride, 76);
+    assert_eq!(desc.attributes.len(), 5);
+    assert_eq!(desc.attributes[4].shader_location, 9);
+    assert_eq!(desc.attributes[4].offset, 64);
+}
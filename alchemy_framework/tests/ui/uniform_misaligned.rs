@@ -0,0 +1,11 @@
+use alchemy_framework::camera::Uniform;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Uniform)]
+struct Misaligned {
+    position: [f32; 3],
+    normal: [f32; 3],
+    _padding: [f32; 2],
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use alchemy_framework::camera::Uniform;
+
+#[repr(align(16))]
+#[derive(Copy, Clone, Uniform)]
+struct NotReprC {
+    color: [f32; 4],
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use alchemy_framework::camera::Uniform;
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Uniform)]
+struct Unpadded {
+    color: [f32; 4],
+    intensity: f32,
+}
+
+fn main() {}
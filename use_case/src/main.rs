@@ -1,4 +1,5 @@
 use alchemy_framework as alchemy;
+use alchemy::camera::CameraControl;
 use winit::{
     event::*,
     event_loop::{ControlFlow},
@@ -7,13 +8,15 @@ use winit::{
 
 fn main() {
     let config = alchemy::graphics::WindowConfig::new(800.0, 800.0, "Hello Wolrd".to_string());
-    <GameEngine as alchemy::graphics::App>::run(config).expect("something went wrong");
+    if let Err(e) = <GameEngine as alchemy::graphics::App>::run(config) {
+        eprintln!("Failed to start: {}", e);
+        std::process::exit(1);
+    }
 }
 
 pub struct GameEngine<>{
     camera: alchemy_framework::camera::CameraObject,
     //pub camera_gpu_object: alchemy_framework::camera::GPUObject<alchemy_framework::camera::Uniforms>,
-    mouse_pressed: bool,
 }
 
 impl alchemy::graphics::App for GameEngine{
@@ -25,29 +28,11 @@ impl alchemy::graphics::App for GameEngine{
  
         Self{
             camera,
-            mouse_pressed: false,
         }
     }
 
-    fn on_load(&mut self, app: &mut alchemy::graphics::AppWindow) { 
-        let uniform_bind_group_layout = app.gpu.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStage::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }
-            ],
-            label: Some("uniform_bind_group_layout"),
-        });
-
-        let camera_gpu_object = alchemy_framework::camera::GPUObject::new(&app.gpu.device, uniform_bind_group_layout, 
-            self.camera.uniforms, 0, "camera");
+    fn on_load(&mut self, app: &mut alchemy::graphics::AppWindow) {
+        let camera_gpu_object = alchemy_framework::camera::GPUObject::from_uniform(&app.gpu.device, self.camera.uniforms, 0, "camera");
         let be = alchemy::gpu::BasicEffect::new(&app.gpu, camera_gpu_object);
         app.gpu.add_effect(be);
     }
@@ -55,16 +40,18 @@ impl alchemy::graphics::App for GameEngine{
     fn on_update(&mut self, app: &mut alchemy::graphics::AppWindow, delta: std::time::Duration) { 
         self.camera.controller.update_camera(&mut self.camera.camera, delta);
         self.camera.update();
-        app.gpu.get_effect().write_camera_buffer(&app.gpu, self.camera.uniforms);
+        if let Ok(effect) = app.gpu.get_effect::<alchemy::gpu::BasicEffect>() {
+            effect.write_camera_buffer(&app.gpu, self.camera.uniforms);
+        }
     }
 
-    fn on_draw(&self, app: &mut alchemy::graphics::AppWindow, control_flow: &mut winit::event_loop::ControlFlow) { 
+    fn on_draw(&self, app: &mut alchemy::graphics::AppWindow, control_flow: &mut winit::event_loop::ControlFlow, _alpha: f32) { 
         match app.gpu.render()  {
             Ok(_) => {}
             // Recreate the swap_chain if lost
-            Err(wgpu::SwapChainError::Lost) => app.gpu.resize(app.gpu.size),
+            Err(alchemy::Error::SwapChain(wgpu::SwapChainError::Lost)) => app.gpu.resize(app.gpu.size),
             // The system is out of memory, we should probably quit
-            Err(wgpu::SwapChainError::OutOfMemory) => *control_flow = ControlFlow::Exit,
+            Err(alchemy::Error::SwapChain(wgpu::SwapChainError::OutOfMemory)) => *control_flow = ControlFlow::Exit,
             // All other errors (Outdated, Timeout) should be resolved by the next frame
             Err(e) => eprintln!("{:?}", e),
         }
@@ -81,23 +68,22 @@ impl alchemy::graphics::App for GameEngine{
                 self.camera.controller.process_scroll(delta);
                 true
             }
-            DeviceEvent::Button {
-                button: 1, // Left Mouse Button
-                state,
-            } => {
-                self.mouse_pressed = *state == ElementState::Pressed;
-                true
-            }
+            DeviceEvent::Button { button, state } => self.camera.controller.process_button(*button, *state),
             DeviceEvent::MouseMotion { delta } => {
-                if self.mouse_pressed {
-                    self.camera.controller.process_mouse(delta.0, delta.1);
-                }
+                self.camera.controller.process_mouse(delta.0, delta.1);
                 true
             }
             _ => false,
         };
     }
 
+    fn on_window_event(&mut self, event: &winit::event::WindowEvent) {
+        //Keys held when focus moved elsewhere are never released, so stop moving.
+        if let WindowEvent::Focused(false) = event {
+            self.camera.controller.reset();
+        }
+    }
+
     fn on_resize(&mut self, physical_size: winit::dpi::PhysicalSize<u32>) 
     { 
         self.camera.resize(physical_size);